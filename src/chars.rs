@@ -0,0 +1,70 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// Characters beyond letters and space that can be enabled with --chars.
+pub const PUNCTUATION: &str = ",.;:!?'\"-";
+pub const DIGITS: &str = "0123456789";
+pub const SYMBOLS: &str = "()[]{}<>=+-*/\\&|_#@$%^~`";
+
+pub const CLASSES: [&str; 3] = ["punct", "digits", "symbols"];
+pub const INJECT_MODES: [&str; 3] = ["wrap", "trail", "numbers"];
+
+// How likely each injection mode is applied to an ngram of a lesson.
+const INJECT_CHANCE: f64 = 0.5;
+
+const BRACKETS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+const TRAILING: [char; 6] = [',', '.', ';', ':', '!', '?'];
+
+// Returns the character classes needed for the given --chars and --inject arguments,
+// since every injection mode needs the characters it injects.
+pub fn enabled_classes(chars: &[String], inject: &[String]) -> Vec<String> {
+    let mut classes: Vec<String> = chars.to_vec();
+    for mode in inject {
+        let class = match mode.as_str() {
+            "wrap" => "symbols",
+            "trail" => "punct",
+            "numbers" => "digits",
+            &_ => continue,
+        };
+        if !classes.iter().any(|c| c == class) {
+            classes.push(class.to_string());
+        }
+    }
+    classes
+}
+
+// Returns whether c can be typed in a lesson. Letters and space always can.
+pub fn is_allowed(c: char, classes: &[String]) -> bool {
+    if c.is_alphabetic() || c == ' ' {
+        return true;
+    }
+    classes.iter().any(|class| match class.as_str() {
+        "punct" => PUNCTUATION.contains(c),
+        "digits" => DIGITS.contains(c),
+        "symbols" => SYMBOLS.contains(c),
+        &_ => false,
+    })
+}
+
+// Applies the wrap and trail injection modes to an ngram, each with INJECT_CHANCE.
+pub fn decorate(ngram: &str, inject: &[String], rng: &mut impl Rng) -> String {
+    let mut token = ngram.to_string();
+    if inject.iter().any(|m| m == "wrap") && rng.gen_bool(INJECT_CHANCE) {
+        let (open, close) = BRACKETS.choose(rng).unwrap();
+        token = format!("{}{}{}", open, token, close);
+    }
+    if inject.iter().any(|m| m == "trail") && rng.gen_bool(INJECT_CHANCE) {
+        token.push(*TRAILING.choose(rng).unwrap());
+    }
+    token
+}
+
+// With the numbers injection mode, returns a number of one to four digits to put
+// after an ngram, with INJECT_CHANCE.
+pub fn number_token(inject: &[String], rng: &mut impl Rng) -> Option<String> {
+    if !inject.iter().any(|m| m == "numbers") || !rng.gen_bool(INJECT_CHANCE) {
+        return None;
+    }
+    let digits = rng.gen_range(1..=4);
+    Some((0..digits).map(|_| *DIGITS.as_bytes().choose(rng).unwrap() as char).collect())
+}
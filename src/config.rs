@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+
+use crate::Args;
+
+// The profile used when no --profile is given, if the config file has one.
+pub const DEFAULT_PROFILE: &str = "default";
+
+// The config file, a set of named profiles.
+//
+// # Example file content:
+// ```
+// [profiles.default]
+// acc = 96
+//
+// [profiles.colemak-learning]
+// emu-out = "colemakdh"
+// show-ortho = true
+// fingers = true
+// top = 25
+// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    profiles: HashMap<String, Profile>,
+}
+
+// The settings of a profile, named like the command line flags. Settings that are
+// not in the profile keep their default.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Profile {
+    n: Option<String>,
+    top: Option<i32>,
+    combi: Option<i32>,
+    rep: Option<i32>,
+    wpm: Option<i32>,
+    acc: Option<i32>,
+    chars: Option<Vec<String>>,
+    inject: Option<Vec<String>>,
+    time: Option<i32>,
+    progress: Option<i32>,
+    on_fail: Option<String>,
+    max_retries: Option<i32>,
+    sample: Option<String>,
+    explore: Option<i32>,
+    emu_in: Option<String>,
+    emu_out: Option<String>,
+    nokitty: Option<bool>,
+    show_ortho: Option<bool>,
+    fingers: Option<bool>,
+    heatmap: Option<String>,
+    nokb: Option<bool>,
+    graphs: Option<bool>,
+    nohistory: Option<bool>,
+    cat: Option<bool>,
+}
+
+// Sets every arg from the profile, unless it was given on the command line.
+macro_rules! apply_profile {
+    ($args:expr, $matches:ident, $profile:ident, $($field:ident),* $(,)?) => {
+        $(
+            if let Some(value) = &$profile.$field {
+                if $matches.value_source(stringify!($field)) != Some(ValueSource::CommandLine) {
+                    $args.$field = value.clone();
+                }
+            }
+        )*
+    };
+}
+
+// Returns the path of the config file inside the XDG config directory,
+// or None if there is no config directory on this platform.
+pub fn config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("ngrrram").join("config.toml"))
+}
+
+fn load(path: &Path) -> Result<Config, String> {
+    let content = std::fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    toml::from_str(&content).map_err(|err| format!("Invalid config file {}: {}", path.display(), err))
+}
+
+// Applies the profile selected with --profile, or the default profile if there is one,
+// to the args. Flags given on the command line win over the profile.
+pub fn apply(args: &mut Args, matches: &ArgMatches) -> Result<(), String> {
+    let path = config_path();
+    let config = match &path {
+        Some(path) if path.exists() => load(path)?,
+        _ if args.profile.is_empty() => return Ok(()),
+        _ => return Err(format!("There is no config file to load profile {} from.", args.profile)),
+    };
+
+    let name = if args.profile.is_empty() { DEFAULT_PROFILE } else { args.profile.as_str() };
+    let Some(profile) = config.profiles.get(name) else {
+        if args.profile.is_empty() {
+            return Ok(());
+        }
+        let mut names: Vec<&str> = config.profiles.keys().map(|n| n.as_str()).collect();
+        names.sort();
+        return Err(match names.is_empty() {
+            true => format!("Unknown profile: {}. The config file has no profiles.", name),
+            false => format!("Unknown profile: {}. The config file has these profiles: {}.", name, names.join(", ")),
+        });
+    };
+
+    apply_profile!(
+        args, matches, profile,
+        n, top, combi, rep, wpm, acc, chars, inject, time, progress, on_fail, max_retries, sample, explore,
+        emu_in, emu_out, nokitty, nohistory,
+    );
+    apply_profile!(args.display, matches, profile, show_ortho, fingers, heatmap, nokb, graphs, cat);
+    Ok(())
+}
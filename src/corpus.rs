@@ -0,0 +1,80 @@
+use ngrrram::ngrams::{count_ngrams, format_list, CorpusOptions, Unit};
+
+#[derive(clap::Args)]
+pub struct CorpusArgs {
+    #[arg(required = true, value_name = "files", help = "the text files to analyze.")]
+    files: Vec<String>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "2",
+        value_name = "2|3|4|w",
+        help = "count bi-(2), tri-(3), tetragrams(4) or (w)ords."
+    )]
+    n: String,
+
+    #[arg(long, action, help = "lowercase the text before counting.")]
+    fold_case: bool,
+
+    #[arg(
+        long,
+        value_name = "chars",
+        help = "only count ngrams made of these characters. defaults to all alphabetic characters."
+    )]
+    alphabet: Option<String>,
+
+    #[arg(
+        long,
+        default_value = "1",
+        value_name = "number",
+        help = "drop ngrams that occur less often than this."
+    )]
+    min_count: usize,
+
+    #[arg(
+        short,
+        long,
+        value_name = "file",
+        help = "write the list to this file instead of stdout."
+    )]
+    out: Option<String>,
+}
+
+// Runs the corpus subcommand.
+pub fn run(args: &CorpusArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let unit = match args.n.as_str() {
+        "2" => Unit::Chars(2),
+        "3" => Unit::Chars(3),
+        "4" => Unit::Chars(4),
+        "w" => Unit::Words,
+        &_ => return Err(format!("Invalid argument for n: {}. Use 2, 3, 4 or w.", args.n).into()),
+    };
+    let options = CorpusOptions {
+        fold_case: args.fold_case,
+        alphabet: args.alphabet.as_ref().map(|a| a.chars().collect()),
+        min_count: args.min_count,
+    };
+
+    let mut text = String::new();
+    for file in &args.files {
+        let content = std::fs::read_to_string(file).map_err(|err| format!("Could not read {}: {}", file, err))?;
+        text.push_str(&content);
+        text.push('\n');
+    }
+
+    let counted = count_ngrams(&text, unit, &options);
+    if counted.is_empty() {
+        return Err("No ngrams found. Check --alphabet and --min-count.".into());
+    }
+
+    let list = format_list(&counted);
+    match &args.out {
+        Some(path) => {
+            std::fs::write(path, list)?;
+            println!("Wrote {} ngrams to {}.", counted.len(), path);
+        }
+        None => print!("{}", list),
+    }
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::{KeyResult, NgramResult, TransitionResult};
+use crate::session::{Options, Session};
+
+// Bump this whenever the format of the history file changes in an incompatible way.
+pub const HISTORY_VERSION: u32 = 1;
+
+// The first line of the history file, every following line is a LessonRecord.
+//
+// # Example file content:
+// ```
+// {"version":1}
+// {"timestamp":1760000000,"wpm":52,"acc":97,"success":true,...}
+// {"timestamp":1760000031,"wpm":48,"acc":91,"success":false,...}
+// ```
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct History {
+    pub version: u32,
+    pub lessons: Vec<LessonRecord>,
+}
+
+// The result of a single finished lesson.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LessonRecord {
+    pub timestamp: u64, // seconds since the unix epoch
+    pub wpm: i32,
+    pub acc: i32,
+    pub success: bool,
+    #[serde(default)]
+    pub millis: u64, // time from the first to the last keystroke
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_wpm: Option<i32>, // only for timed tests, where wpm is the net wpm
+    #[serde(default)]
+    pub retry: i32, // how often the same lesson was repeated before this attempt
+    #[serde(default)]
+    pub lesson: i32, // the number of the lesson in its session, generated again by the seed
+    pub ngrams: Vec<String>,
+    #[serde(default)]
+    pub ngram_results: Vec<NgramResult>,
+    #[serde(default)]
+    pub transitions: Vec<TransitionResult>,
+    #[serde(default)]
+    pub key_results: Vec<KeyResult>,
+    pub options: Options, // of the session, with its seed, so the lesson can be generated again
+    pub tier: Tier,
+}
+
+// The ngram list, pool and combination a lesson was played at. They start at the
+// options and move on with progress.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tier {
+    pub n: String,
+    pub top: i32,
+    pub combi: i32,
+}
+
+impl Tier {
+    pub fn of(session: &Session) -> Self {
+        Tier {
+            n: session.n.clone(),
+            top: session.top,
+            combi: session.combi,
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            version: HISTORY_VERSION,
+            lessons: Vec::new(),
+        }
+    }
+}
+
+pub fn now_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Returns the path of the history file inside the XDG data directory,
+// or None if there is no data directory on this platform.
+pub fn history_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("ngrrram").join("history.jsonl"))
+}
+
+// Loads the history file at path. A missing file is treated as an empty history.
+pub fn load(path: &Path) -> Result<History, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        return Ok(History::default());
+    }
+    let file = std::fs::read_to_string(path)?;
+    let mut lines = file.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(History::default());
+    };
+    let header: Header = serde_json::from_str(header)?;
+    if header.version > HISTORY_VERSION {
+        return Err(format!(
+            "{} was written by a newer version of ngrrram (history version {}, supported up to {}).",
+            path.display(),
+            header.version,
+            HISTORY_VERSION
+        )
+        .into());
+    }
+    let mut history = History::default();
+    for (line_number, line) in lines {
+        let lesson = serde_json::from_str(line).map_err(|err| format!("line {}: {}", line_number + 1, err))?;
+        history.lessons.push(lesson);
+    }
+    Ok(history)
+}
+
+// Appends a finished lesson to the history file at path, starting the file if there is none.
+// Only the new line is written, so this stays cheap however long the history gets.
+pub fn append(path: &Path, lesson: &LessonRecord) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = String::new();
+    if file.metadata()?.len() == 0 {
+        line.push_str(&serde_json::to_string(&Header { version: HISTORY_VERSION })?);
+        line.push('\n');
+    }
+    line.push_str(&serde_json::to_string(lesson)?);
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::layout::KbEmulator;
+use crate::session::Key;
+
+// The keys a frontend reports, of which the session uses chars, enter and backspace.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyCode {
+    Char(char), // with shift, caps lock and the system layout applied
+    Enter,
+    Tab,
+    Backspace,
+    Esc,
+}
+
+// A key press along with the key at the same physical position on a us qwerty keyboard,
+// if the terminal reports it. That position is what makes emulation independent of the
+// layout the system is set to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct KeyInput {
+    pub code: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub base: Option<char>,
+    pub shifted: bool, // whether shift was held, for chars it is already applied to the code
+    pub caps_lock: bool, // only known with the kitty keyboard protocol
+}
+
+impl KeyInput {
+    // A press of the key without modifiers.
+    pub fn new(code: KeyCode) -> Self {
+        KeyInput {
+            code,
+            ctrl: false,
+            alt: false,
+            base: None,
+            shifted: false,
+            caps_lock: false,
+        }
+    }
+
+    // Whether this is <esc> or <ctrl-c>.
+    pub fn is_quit(&self) -> bool {
+        self.code == KeyCode::Esc || (self.ctrl && !self.alt && self.code == KeyCode::Char('c'))
+    }
+
+    // Translates a key press into a key of the session, typed on the emulated layout if
+    // there is an emulator. Returns None for keys the session does not use.
+    pub fn to_session_key(&self, emulator: Option<&KbEmulator>) -> Option<Key> {
+        match (self.code, self.ctrl, self.alt) {
+            (KeyCode::Backspace, false, true) | (KeyCode::Char('h'), true, false) => Some(Key::DeleteWord),
+            (KeyCode::Backspace, _, _) => Some(Key::Backspace),
+            // the base layout key is the physical key, independent of the system layout
+            (KeyCode::Char(c), _, _) => match (emulator, self.base) {
+                (Some(emulator), Some(base)) => match emulator.translate_key(base, self.shifted) {
+                    // caps lock flips the case of letters, like on a real keyboard with the emulated layout
+                    Some(emulated) if self.caps_lock && emulated.is_alphabetic() => Some(Key::Char(flip_case(emulated))),
+                    emulated => Some(Key::Char(emulated.unwrap_or(c))),
+                },
+                (Some(emulator), None) => Some(Key::Char(emulator.translate(c).unwrap_or(c))),
+                (None, _) => Some(Key::Char(c)),
+            },
+            // treat enter as space
+            (KeyCode::Enter, _, _) => Some(Key::Char(' ')),
+            _ => None,
+        }
+    }
+}
+
+// Where key presses come from, like the terminal of the ngrrram binary or a script.
+pub trait InputSource {
+    // Waits up to timeout for the next key press. Releases are not reported.
+    fn poll_key(&mut self, timeout: Duration) -> Result<Option<KeyInput>, Box<dyn std::error::Error>>;
+
+    // Whether key presses come with their base layout key.
+    fn reports_base_keys(&self) -> bool;
+
+    // Restores whatever the source changed about the terminal.
+    fn cleanup(&self) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+// Plays key presses from a script, each after its delay since the previous one. Like a
+// terminal without the kitty keyboard protocol, it reports no base layout keys. Once the
+// script is over, it keeps pressing <esc>, so the program ends like after a session.
+pub struct ScriptedInput {
+    keys: VecDeque<(Duration, KeyInput)>,
+    last_key_time: Option<Instant>, // the script starts with the first poll
+}
+
+impl ScriptedInput {
+    pub fn new(keys: Vec<(Duration, KeyInput)>) -> Self {
+        ScriptedInput {
+            keys: keys.into(),
+            last_key_time: None,
+        }
+    }
+
+    // Loads a script with one key press per line: the milliseconds since the previous key
+    // and the key. A key is a single character or one of space, enter, tab, backspace and
+    // esc, optionally prefixed with ctrl- and alt-. Lines starting with # are comments.
+    //
+    // # Example file content:
+    // ```
+    // # "the" with a typo, fixed with a word delete
+    // 0 t
+    // 150 g
+    // 120 e
+    // 300 ctrl-h
+    // 200 t
+    // 140 h
+    // 130 e
+    // ```
+    pub fn load(path: &str) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+        ScriptedInput::parse(path, &file)
+    }
+
+    // Parses the content of a script, see `load`. Errors name the script by path.
+    pub fn parse(path: &str, script: &str) -> Result<Self, String> {
+        let mut keys = Vec::new();
+        for (line_number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let columns: Vec<&str> = line.split_whitespace().collect();
+            let (Some(delay), Some(key), None) = (columns.first(), columns.get(1), columns.get(2)) else {
+                return Err(format!("{}:{}: expected the milliseconds since the previous key and the key.", path, line_number + 1));
+            };
+            let delay = delay.parse::<u64>().map_err(|_| format!(
+                "{}:{}: invalid delay {}, expected a number of milliseconds.",
+                path,
+                line_number + 1,
+                delay
+            ))?;
+            let key = parse_key_name(key).ok_or(format!("{}:{}: unknown key {}.", path, line_number + 1, key))?;
+            keys.push((Duration::from_millis(delay), key));
+        }
+        Ok(ScriptedInput::new(keys))
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll_key(&mut self, timeout: Duration) -> Result<Option<KeyInput>, Box<dyn std::error::Error>> {
+        let Some((delay, _)) = self.keys.front() else {
+            return Ok(Some(KeyInput::new(KeyCode::Esc)));
+        };
+        let now = Instant::now();
+        let due = *self.last_key_time.get_or_insert(now) + *delay;
+        if due > now + timeout {
+            std::thread::sleep(timeout);
+            return Ok(None);
+        }
+        std::thread::sleep(due.saturating_duration_since(now));
+        // the next delay counts from when the key was due, so a slow frame does not add up
+        self.last_key_time = Some(due);
+        Ok(self.keys.pop_front().map(|(_, key)| key))
+    }
+
+    fn reports_base_keys(&self) -> bool {
+        false
+    }
+
+    fn cleanup(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
+
+// Parses a key of a script, see `ScriptedInput::load`.
+fn parse_key_name(name: &str) -> Option<KeyInput> {
+    let (mut ctrl, mut alt) = (false, false);
+    let mut rest = name;
+    // a lone - is the key itself, not a modifier
+    while rest.len() > 1 {
+        if let Some(r) = rest.strip_prefix("ctrl-") {
+            ctrl = true;
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("alt-") {
+            alt = true;
+            rest = r;
+        } else {
+            break;
+        }
+    }
+    let code = match rest {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "esc" => KeyCode::Esc,
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(KeyInput { ctrl, alt, ..KeyInput::new(code) })
+}
+
+fn flip_case(c: char) -> char {
+    match c.is_uppercase() {
+        true => c.to_lowercase().next().unwrap_or(c),
+        false => c.to_uppercase().next().unwrap_or(c),
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::iter;
 
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 pub enum Layout {
     Qwerty,
@@ -10,28 +11,47 @@ pub enum Layout {
     Dvorak,
     Colemak,
     ColemakDH,
+    // a layout loaded from a file, in the same format as the arrays of the layout! macro.
+    // The first array is the unshifted layer, the second one the shift layer.
+    Custom(Vec<char>, Vec<char>),
 }
 
 pub struct KbEmulator {
     input_layout: HashMap<char, u8>,
+    input_shift_layout: HashMap<char, u8>,
     output_layout: HashMap<u8, char>,
+    output_shift_layout: HashMap<u8, char>,
 }
 
 impl KbEmulator {
-    pub fn new(input: Layout, output: Layout) -> Self {
-        let input_layout = get_layout(input);
-        let output_layout = make_output_map(get_layout(output));
+    pub fn new(input: &Layout, output: &Layout) -> Self {
+        let (input_layout, input_shift_layout) = get_layout(input);
+        let (output_layout, output_shift_layout) = get_layout(output);
 
         KbEmulator {
             input_layout,
-            output_layout,
+            input_shift_layout,
+            output_layout: make_output_map(output_layout),
+            output_shift_layout: make_output_map(output_shift_layout),
         }
     }
 
+    // Translates a typed char. Whether shift was held follows from the layer the char is on.
     pub fn translate(&self, input: char) -> Option<char> {
-        self.input_layout
+        if let Some(&v) = self.input_layout.get(&input) {
+            return Some(self.output_layout[&v]);
+        }
+        self.input_shift_layout
             .get(&input)
-            .map(|&v| self.output_layout[&v])
+            .map(|&v| self.output_shift_layout[&v])
+    }
+
+    // Translates an unshifted key of the input layout, honoring the shift state.
+    pub fn translate_key(&self, key: char, shifted: bool) -> Option<char> {
+        let output_layout = if shifted { &self.output_shift_layout } else { &self.output_layout };
+        self.input_layout
+            .get(&key)
+            .map(|&v| output_layout[&v])
     }
 }
 
@@ -39,7 +59,8 @@ fn make_output_map(layout: HashMap<char, u8>) -> HashMap<u8, char> {
     layout.into_iter().map(|(k, v)| (v, k)).collect()
 }
 
-fn get_layout(layout: Layout) -> HashMap<char, u8> {
+// Returns the keymaps of the unshifted and the shift layer of a layout.
+fn get_layout(layout: &Layout) -> (HashMap<char, u8>, HashMap<char, u8>) {
     match layout {
         Layout::Qwerty => qwerty(),
         Layout::Qwertz => qwertz(),
@@ -47,35 +68,329 @@ fn get_layout(layout: Layout) -> HashMap<char, u8> {
         Layout::Dvorak => dvorak(),
         Layout::Colemak => colemak(),
         Layout::ColemakDH => colemak_dh(),
+        Layout::Custom(keymap, shift_keymap) => (make_keymap(keymap), make_keymap(shift_keymap)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+    Thumb,
+}
+
+impl Finger {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Finger::LeftPinky => "left pinky",
+            Finger::LeftRing => "left ring",
+            Finger::LeftMiddle => "left middle",
+            Finger::LeftIndex => "left index",
+            Finger::RightIndex => "right index",
+            Finger::RightMiddle => "right middle",
+            Finger::RightRing => "right ring",
+            Finger::RightPinky => "right pinky",
+            Finger::Thumb => "thumb",
+        }
+    }
+}
+
+// Which finger presses the key in each column of a row, for the rows of the layout! macro.
+// On a row staggered keyboard, the 6 is reached with the left index finger. On an
+// ortholinear keyboard every column belongs to one finger.
+const STAGGER_FINGERS: [[Finger; 12]; 4] = {
+    use Finger::*;
+    [
+        [LeftPinky, LeftRing, LeftMiddle, LeftIndex, LeftIndex, LeftIndex, RightIndex, RightMiddle, RightRing, RightPinky, RightPinky, RightPinky],
+        [LeftPinky, LeftRing, LeftMiddle, LeftIndex, LeftIndex, RightIndex, RightIndex, RightMiddle, RightRing, RightPinky, RightPinky, RightPinky],
+        [LeftPinky, LeftRing, LeftMiddle, LeftIndex, LeftIndex, RightIndex, RightIndex, RightMiddle, RightRing, RightPinky, RightPinky, RightPinky],
+        [LeftPinky, LeftRing, LeftMiddle, LeftIndex, LeftIndex, RightIndex, RightIndex, RightMiddle, RightRing, RightPinky, RightPinky, RightPinky],
+    ]
+};
+const ORTHO_FINGERS: [Finger; 12] = {
+    use Finger::*;
+    [LeftPinky, LeftRing, LeftMiddle, LeftIndex, LeftIndex, RightIndex, RightIndex, RightMiddle, RightRing, RightPinky, RightPinky, RightPinky]
+};
+
+fn finger_at(row: usize, column: usize, is_ortho: bool) -> Finger {
+    if is_ortho {
+        ORTHO_FINGERS[column]
+    } else {
+        STAGGER_FINGERS[row][column]
+    }
+}
+
+// Where a key is drawn in a rendered keyboard, in characters.
+pub struct KeyCell {
+    pub line: usize,
+    pub column: usize,
+    pub width: usize,
+}
+
+// A rendered keyboard along with the cell of every key, so keys can be highlighted.
+pub struct Keyboard {
+    pub lines: Vec<String>,
+    cells: HashMap<u8, KeyCell>, // by key position, as in the keymaps
+    fingers: HashMap<u8, Finger>,
+    positions: HashMap<char, u8>, // the chars of both layers
+}
+
+impl Keyboard {
+    // Renders the keyboard of a layout. The compact rendering leaves out the key frames,
+    // for terminals too small for the full keyboard.
+    pub fn new(layout: &Layout, show_ortho: bool, compact: bool) -> Self {
+        let keymap = match layout {
+            Layout::Qwerty => keys_qwerty(),
+            Layout::Qwertz => keys_qwertz(),
+            Layout::Azerty => keys_azerty(),
+            Layout::Dvorak => keys_dvorak(),
+            Layout::Colemak => keys_colemak(),
+            Layout::ColemakDH => keys_colemak_dh(),
+            Layout::Custom(keymap, _) => keymap,
+        };
+        let (lines, cells) = if compact {
+            render_compact_map(keymap, show_ortho)
+        } else {
+            render_map(keymap, show_ortho)
+        };
+        let fingers = split_rows(keymap)
+            .iter()
+            .enumerate()
+            .flat_map(|(row_index, row)| {
+                row.iter()
+                    .enumerate()
+                    .map(move |(column, (position, _))| (*position, finger_at(row_index, column, show_ortho)))
+            })
+            .collect();
+        let (base_positions, shift_positions) = get_layout(layout);
+        let mut positions = shift_positions;
+        positions.extend(base_positions);
+        Keyboard { lines, cells, fingers, positions }
+    }
+
+    // Returns the cell of the key that types c, with or without shift.
+    pub fn cell_of(&self, c: char) -> Option<&KeyCell> {
+        self.positions.get(&c).and_then(|position| self.cells.get(position))
+    }
+
+    // Returns the finger that types c, with or without shift.
+    pub fn finger_of(&self, c: char) -> Option<Finger> {
+        if c == ' ' {
+            return Some(Finger::Thumb);
+        }
+        self.positions.get(&c).and_then(|position| self.fingers.get(position)).copied()
+    }
+
+    // Returns every key that is drawn, along with the finger that presses it.
+    pub fn keys(&self) -> impl Iterator<Item = (&KeyCell, Finger)> {
+        self.cells.iter().map(|(position, cell)| (cell, self.fingers[position]))
+    }
+
+    pub fn width(&self) -> u16 {
+        self.lines.iter().map(|line| line.chars().count()).max().unwrap_or(0) as u16
+    }
+
+    pub fn height(&self) -> u16 {
+        self.lines.len() as u16
+    }
+}
+
+// Returns the built-in layout with the given name.
+pub fn from_name(name: &str) -> Option<Layout> {
+    match name {
+        "qwerty" => Some(Layout::Qwerty),
+        "qwertz" => Some(Layout::Qwertz),
+        "azerty" => Some(Layout::Azerty),
+        "dvorak" => Some(Layout::Dvorak),
+        "colemak" => Some(Layout::Colemak),
+        "colemakdh" => Some(Layout::ColemakDH),
+        &_ => None,
+    }
+}
+
+// The number of keys in each row of a layout, from the number row to the bottom row.
+// These are the rows of the layout! macro, so every layout maps onto the same key positions.
+const ROW_LENGTHS: [usize; 4] = [12, 12, 11, 10];
+const ROW_NAMES: [&str; 4] = ["number", "top", "home", "bottom"];
+
+// The rows of a layout file, also kept in recordings so they play back without the file.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LayoutFile {
+    pub rows: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shift_rows: Option<Vec<String>>,
+}
+
+impl LayoutFile {
+    // The rows of a layout loaded from a file, None for the built-in layouts, which
+    // are known by name.
+    pub fn of(layout: &Layout) -> Option<Self> {
+        let Layout::Custom(keymap, shift_keymap) = layout else {
+            return None;
+        };
+        let rows = |map: &[char]| split_rows(map).iter().map(|row| row.iter().map(|(_, key)| key).collect()).collect();
+        Some(LayoutFile { rows: rows(keymap), shift_rows: Some(rows(shift_keymap)) })
     }
 }
 
-pub fn get_layout_string(layout: &Layout, show_ortho: bool) -> String {
-    let render = match layout {
-        Layout::Qwerty => render_qwerty,
-        Layout::Qwertz => render_qwertz,
-        Layout::Azerty => render_azerty,
-        Layout::Dvorak => render_dvorak,
-        Layout::Colemak => render_colemak,
-        Layout::ColemakDH => render_colemak_dh,
+// Loads a layout from a TOML or JSON file (picked by the file extension).
+// The file lists the keys of each row as a string, like the layout! macro does.
+// The shift layer is optional. Without it, letters are shifted to uppercase and
+// other keys stay the same.
+//
+// # Example file content:
+// ```
+// rows = [
+//     "1234567890-=",
+//     "qdrwbjfup;[]",
+//     "ashtgyneoi'",
+//     "zxmcvkl,./",
+// ]
+// shift_rows = [
+//     "!@#$%^&*()_+",
+//     "QDRWBJFUP:{}",
+//     "ASHTGYNEOI\"",
+//     "ZXMCVKL<>?",
+// ]
+// ```
+pub fn load(path: &str) -> Result<Layout, String> {
+    let content = std::fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+    let file: LayoutFile = if path.ends_with(".json") {
+        serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?
+    } else {
+        toml::from_str(&content).map_err(|err| format!("{}: {}", path, err))?
     };
-    render(show_ortho)
+    from_file(&file).map_err(|err| format!("{}: {}", path, err))
 }
 
-fn render_map(map: &[char], is_ortho: bool) -> String {
-    let mut rows = Vec::new();
-    let mut max_row_length = 0;
+// Makes a layout of the rows of a layout file, see `load`.
+pub fn from_file(file: &LayoutFile) -> Result<Layout, String> {
+    let keymap = parse_rows(&file.rows, "")?;
+    let shift_keymap = match &file.shift_rows {
+        Some(shift_rows) => parse_rows(shift_rows, "shift ")?,
+        None => keymap
+            .iter()
+            .map(|key| {
+                let mut upper = key.to_uppercase();
+                match (upper.next(), upper.next()) {
+                    (Some(upper), None) => upper,
+                    _ => *key, // e.g. 'ß' uppercases to "SS", which is not a single key
+                }
+            })
+            .collect(),
+    };
+
+    // a char on the shift layer must not be on the unshifted layer of another key
+    let positions = make_keymap(&keymap);
+    for (index, key) in shift_keymap.iter().enumerate() {
+        if let Some(&position) = positions.get(key) {
+            if *key != '\n' && position as usize != index + 1 {
+                return Err(format!("duplicate key '{}' on the unshifted and the shift layer.", key));
+            }
+        }
+    }
+    Ok(Layout::Custom(keymap, shift_keymap))
+}
 
+// Parses the rows of a layer into the array format of the layout! macro.
+// `layer` prefixes the row names in error messages.
+fn parse_rows(rows: &[String], layer: &str) -> Result<Vec<char>, String> {
+    if rows.len() != ROW_LENGTHS.len() {
+        return Err(format!(
+            "expected {} {}rows ({}), found {}.",
+            ROW_LENGTHS.len(),
+            layer,
+            ROW_NAMES.join(", "),
+            rows.len()
+        ));
+    }
+
+    let mut keymap: Vec<char> = Vec::new();
+    let mut positions: HashMap<char, (usize, usize)> = HashMap::new();
+    for (row_index, row) in rows.iter().enumerate() {
+        let keys: Vec<char> = row.chars().collect();
+        let expected = ROW_LENGTHS[row_index];
+        if keys.len() != expected {
+            let problem = if keys.len() < expected { "keys are missing" } else { "there are too many keys" };
+            return Err(format!(
+                "the {} {}row has {} keys but needs exactly {}, {}.",
+                layer,
+                ROW_NAMES[row_index],
+                keys.len(),
+                expected,
+                problem
+            ));
+        }
+        for (column, key) in keys.into_iter().enumerate() {
+            if key.is_whitespace() {
+                return Err(format!(
+                    "the {} {}row contains whitespace at key {}. Keys can not be whitespace, the space bar is added automatically.",
+                    layer,
+                    ROW_NAMES[row_index],
+                    column + 1
+                ));
+            }
+            if let Some((other_row, other_column)) = positions.insert(key, (row_index, column)) {
+                return Err(format!(
+                    "duplicate key '{}': key {} of the {} {}row and key {} of the {} {}row.",
+                    key,
+                    other_column + 1,
+                    layer,
+                    ROW_NAMES[other_row],
+                    column + 1,
+                    layer,
+                    ROW_NAMES[row_index]
+                ));
+            }
+            keymap.push(key);
+        }
+        keymap.push('\n');
+    }
+    keymap.push(' ');
+    Ok(keymap)
+}
+
+// Splits a keymap into its rows of keys, each key along with its position in the keymap.
+fn split_rows(map: &[char]) -> Vec<Vec<(u8, char)>> {
     // Newline is used as a marker for rows of keys. It is safe since it is not a valid key.
-    for (_, chunk) in &map.iter().chunk_by(|elt| **elt != '\n') {
-        let row: Vec<char> = chunk.copied().collect();
-        let length = row.len();
-        // chunk_by will leave one element chunks of the newline character. Skip these.
-        if length > 1 {
-            rows.push(row);
-            max_row_length = max_row_length.max(length);
+    // Positions start at 1, like in make_keymap.
+    map.iter()
+        .enumerate()
+        .map(|(index, key)| ((index + 1) as u8, *key))
+        .collect::<Vec<(u8, char)>>()
+        // the space bar at the end is not part of any row
+        .split(|(_, key)| *key == '\n')
+        .filter(|row| row.len() > 1)
+        .map(|row| row.to_vec())
+        .collect()
+}
+
+// Renders only the keys, one line per row, without any frames.
+fn render_compact_map(map: &[char], is_ortho: bool) -> (Vec<String>, HashMap<u8, KeyCell>) {
+    let mut lines: Vec<String> = Vec::new();
+    let mut cells: HashMap<u8, KeyCell> = HashMap::new();
+
+    // The first row is skipped because the number rows are not used or shown.
+    for (row_index, row) in split_rows(map).iter().enumerate().skip(1) {
+        let indent = if is_ortho { 0 } else { row_index - 1 };
+        for (column, (position, _)) in row.iter().enumerate() {
+            cells.insert(*position, KeyCell { line: lines.len(), column: indent + 2 * column, width: 1 });
         }
+        lines.push(format!("{}{}", " ".repeat(indent), row.iter().map(|(_, key)| key).join(" ")));
     }
+    (lines, cells)
+}
+
+fn render_map(map: &[char], is_ortho: bool) -> (Vec<String>, HashMap<u8, KeyCell>) {
+    let rows = split_rows(map);
+    let max_row_length = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut cells: HashMap<u8, KeyCell> = HashMap::new();
 
     let box_bottom = ('└', '─', '┴', '┘');
     let box_normal = if is_ortho {
@@ -107,13 +422,18 @@ fn render_map(map: &[char], is_ortho: bool) -> String {
             ));
         }
 
+        let indent = if is_ortho { 0 } else { row_index - 1 };
+        // every key is drawn as "│ k ", so the cell between the frames starts after the "│"
+        for (column, (position, _)) in row.iter().enumerate() {
+            cells.insert(*position, KeyCell { line: lines.len(), column: indent + 4 * column + 1, width: 3 });
+        }
+        let keys = row.iter().map(|(_, key)| key);
         let line = if is_ortho {
-            render_key_row(row.iter().pad_using(max_row_length, |_| &'△'))
+            render_key_row(keys.pad_using(max_row_length, |_| &'△'))
         } else {
-            let line = render_key_row(row.iter());
-            format!("{}{line}", " ".repeat(row_index - 1))
+            render_key_row(keys)
         };
-        lines.push(line);
+        lines.push(format!("{}{line}", " ".repeat(indent)));
 
         // Every row but the last one has connectors to the next row.
         let (begin, down, middle, end) = if row_index == rows.len() - 1 {
@@ -122,13 +442,9 @@ fn render_map(map: &[char], is_ortho: bool) -> String {
             box_normal
         };
         let line = render_frame_row(column_count, begin, down, middle, end);
-        if is_ortho {
-            lines.push(line);
-        } else {
-            lines.push(format!("{}{line}", " ".repeat(row_index - 1)));
-        }
+        lines.push(format!("{}{line}", " ".repeat(indent)));
     }
-    lines.join("\n")
+    (lines, cells)
 }
 
 fn render_frame_row(
@@ -141,8 +457,8 @@ fn render_frame_row(
     [begin]
         .into_iter()
         .chain(
-            iter::repeat([down, '─', '─', middle])
-                .take(column_count - 1) // one less to account for the final box with the end connector.
+            // one less to account for the final box with the end connector.
+            iter::repeat_n([down, '─', '─', middle], column_count - 1)
                 .flatten(),
         )
         .chain([down, '─', '─', end])
@@ -163,15 +479,15 @@ fn make_keymap(map: &[char]) -> HashMap<char, u8> {
 }
 
 macro_rules! layout {
-    // layout name and array of keys
-    ($func_name:ident, $keymap:expr) => {
-        fn $func_name() -> HashMap<char, u8> {
-            make_keymap(&$keymap)
+    // layout name, array of keys and array of the same keys with shift held
+    ($func_name:ident, $keymap:expr, $shift_keymap:expr) => {
+        fn $func_name() -> (HashMap<char, u8>, HashMap<char, u8>) {
+            (make_keymap(&$keymap), make_keymap(&$shift_keymap))
         }
 
         paste::item! {
-            fn [< render_ $func_name >] (show_ortho: bool) -> String {
-                render_map(&$keymap, show_ortho)
+            fn [< keys_ $func_name >] () -> &'static [char] {
+                &$keymap
             }
         }
     };
@@ -185,6 +501,13 @@ layout! {
         'a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', ';', '\'', '\n',
         'z', 'x', 'c', 'v', 'b', 'n', 'm', ',', '.', '/', '\n',
         ' ',
+    ],
+    [
+        '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', '\n',
+        'Q', 'W', 'E', 'R', 'T', 'Y', 'U', 'I', 'O', 'P', '{', '}', '\n',
+        'A', 'S', 'D', 'F', 'G', 'H', 'J', 'K', 'L', ':', '"', '\n',
+        'Z', 'X', 'C', 'V', 'B', 'N', 'M', '<', '>', '?', '\n',
+        ' ',
     ]
 }
 
@@ -198,6 +521,13 @@ layout! {
         'a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'ö', 'ä', '\n',
         'y', 'x', 'c', 'v', 'b', 'n', 'm', ',', '.', '-', '\n',
         ' ',
+    ],
+    [
+        '!', '"', '§', '$', '%', '&', '/', '(', ')', '=', '?', '`', '\n',
+        'Q', 'W', 'E', 'R', 'T', 'Z', 'U', 'I', 'O', 'P', 'Ü', '*', '\n',
+        'A', 'S', 'D', 'F', 'G', 'H', 'J', 'K', 'L', 'Ö', 'Ä', '\n',
+        'Y', 'X', 'C', 'V', 'B', 'N', 'M', ';', ':', '_', '\n',
+        ' ',
     ]
 }
 
@@ -210,6 +540,13 @@ layout! {
         'q', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'm', 'ù', '\n',
         'w', 'x', 'c', 'v', 'b', 'n', ',', ';', ':', '!', '\n',
         ' ',
+    ],
+    [
+        '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '°', '+', '\n',
+        'A', 'Z', 'E', 'R', 'T', 'Y', 'U', 'I', 'O', 'P', '¨', '£', '\n',
+        'Q', 'S', 'D', 'F', 'G', 'H', 'J', 'K', 'L', 'M', '%', '\n',
+        'W', 'X', 'C', 'V', 'B', 'N', '?', '.', '/', '§', '\n',
+        ' ',
     ]
 }
 
@@ -221,6 +558,13 @@ layout! {
         'a', 'o', 'e', 'u', 'i', 'd', 'h', 't', 'n', 's', '-', '\n',
         ';', 'q', 'j', 'k', 'x', 'b', 'm', 'w', 'v', 'z', '\n',
         ' ',
+    ],
+    [
+        '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '{', '}', '\n',
+        '"', '<', '>', 'P', 'Y', 'F', 'G', 'C', 'R', 'L', '?', '+', '\n',
+        'A', 'O', 'E', 'U', 'I', 'D', 'H', 'T', 'N', 'S', '_', '\n',
+        ':', 'Q', 'J', 'K', 'X', 'B', 'M', 'W', 'V', 'Z', '\n',
+        ' ',
     ]
 }
 
@@ -232,6 +576,13 @@ layout! {
         'a', 'r', 's', 't', 'd', 'h', 'n', 'e', 'i', 'o', '\'', '\n',
         'z', 'x', 'c', 'v', 'b', 'k', 'm', ',', '.', '/', '\n',
         ' ',
+    ],
+    [
+        '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', '\n',
+        'Q', 'W', 'F', 'P', 'G', 'J', 'L', 'U', 'Y', ':', '{', '}', '\n',
+        'A', 'R', 'S', 'T', 'D', 'H', 'N', 'E', 'I', 'O', '"', '\n',
+        'Z', 'X', 'C', 'V', 'B', 'K', 'M', '<', '>', '?', '\n',
+        ' ',
     ]
 }
 
@@ -243,5 +594,12 @@ layout! {
         'a', 'r', 's', 't', 'g', 'm', 'n', 'e', 'i', 'o', '\'', '\n',
         'z', 'x', 'c', 'd', 'v', 'k', 'h', ',', '.', '/', '\n',
         ' ',
+    ],
+    [
+        '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', '\n',
+        'Q', 'W', 'F', 'P', 'B', 'J', 'L', 'U', 'Y', ':', '{', '}', '\n',
+        'A', 'R', 'S', 'T', 'G', 'M', 'N', 'E', 'I', 'O', '"', '\n',
+        'Z', 'X', 'C', 'D', 'V', 'K', 'H', '<', '>', '?', '\n',
+        ' ',
     ]
 }
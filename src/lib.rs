@@ -0,0 +1,16 @@
+// The typing trainer without a terminal: lessons, their results and the history.
+// The ngrrram binary puts a tui on top of a Session, other frontends can drive one
+// with key events the same way, from their own input or a script (see input).
+pub mod chars;
+pub mod history;
+pub mod input;
+pub mod layout;
+pub mod ngrams;
+pub mod progress;
+pub mod race;
+pub mod recording;
+pub mod session;
+pub mod stats;
+pub mod summary;
+
+pub use session::{Key, Options, Session, Snapshot};
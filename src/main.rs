@@ -1,14 +1,33 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use ngrrram::input::{self, InputSource};
+use ngrrram::{history, layout, race, recording, summary, Options, Session};
 
 mod cat;
-mod game;
-mod layout;
-mod ngrams;
+mod config;
+mod corpus;
+mod replay;
+mod report;
+mod terminal;
 mod tui;
 
 #[derive(Parser)]
 //#[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        short,
+        long,
+        default_value = "",
+        value_name = "name",
+        help = "use the settings of this profile from the config file. flags override them."
+    )]
+    profile: String,
+
     #[arg(
         short,
         long,
@@ -63,12 +82,83 @@ struct Args {
     )]
     acc: i32,
 
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "punct,digits,symbols",
+        help = "character classes to practice in addition to letters, comma separated."
+    )]
+    chars: Vec<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "wrap,trail,numbers",
+        help = "mix characters into lessons: (wrap) ngrams in brackets, add (trail)ing punctuation or insert (numbers)."
+    )]
+    inject: Vec<String>,
+
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "15|30|60|120",
+        help = "take a timed typing test of this many seconds instead of lessons. 0 disables it."
+    )]
+    time: i32,
+
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "number",
+        help = "advance to a harder tier after passing this many lessons in a row. 0 disables progression."
+    )]
+    progress: i32,
+
+    #[arg(
+        long,
+        default_value = "new",
+        value_name = "new|repeat",
+        help = "what to do after a failed lesson: generate a (new) one or (repeat) it until you pass."
+    )]
+    on_fail: String,
+
+    #[arg(
+        long,
+        default_value = "0",
+        value_name = "number",
+        help = "with --on-fail repeat, move on after this many retries of a lesson. 0 means never give up."
+    )]
+    max_retries: i32,
+
+    #[arg(
+        long,
+        default_value = "uniform",
+        value_name = "uniform|freq|weak",
+        help = "how to choose ngrams for a lesson: (uniform)ly, by real world (freq)uency or biased towards your (weak)est ngrams."
+    )]
+    sample: String,
+
+    #[arg(
+        long,
+        default_value = "20",
+        value_name = "0-100",
+        help = "with --sample weak, the weight in percent every ngram keeps regardless of how well you type it."
+    )]
+    explore: i32,
+
+    #[arg(
+        long,
+        value_name = "number",
+        help = "generate the lessons from this seed, to practice the same lessons again. a random one by default."
+    )]
+    seed: Option<u64>,
+
     #[arg(
         long,
         action,
         default_value = "",
         value_name = "layout",
-        help = "your current keyboard layout. only needed if you want to emulate a different layout. see docs for supported layouts."
+        help = "your current keyboard layout, by name or layout file. only needed for emulation if your terminal lacks the kitty keyboard protocol. see docs for supported layouts."
     )]
     emu_in: String,
 
@@ -77,161 +167,262 @@ struct Args {
         action,
         default_value = "",
         value_name = "layout",
-        help = "the layout you want to emulate. only needed if you want to emulate a different layout. see docs for supported layouts."
+        help = "the layout you want to emulate, by name or layout file. only needed if you want to emulate a different layout. see docs for supported layouts."
     )]
     emu_out: String,
 
     #[arg(
         long,
         action,
-        default_value = "false",
-        value_name = "bool",
-        help = "show keyboard in ortholinear format"
+        help = "pass this flag to not use the kitty keyboard protocol, even if your terminal supports it."
+    )]
+    nokitty: bool,
+
+    #[arg(
+        long,
+        default_value = "",
+        value_name = "file",
+        help = "type the keys of this script instead of reading them from the keyboard, without saving the history. see docs for the format."
     )]
-    show_ortho: bool,
+    script: String,
+
+    #[arg(
+        long,
+        default_value = "",
+        value_name = "file|best",
+        help = "race a ghost through the lessons of a recorded session, or of your (best) one."
+    )]
+    race: String,
 
     #[arg(
         long,
         action,
-        help = "pass this flag to disable the keyboard layout display."
+        help = "pass this flag to disable saving lesson results to the history file."
     )]
-    nokb: bool,
+    nohistory: bool,
 
-    #[arg(long, action, help = "the most important flag. don't practice alone.")]
-    cat: bool,
+    #[command(flatten)]
+    display: tui::DisplayArgs,
 }
 
-struct AppState {
-    current_lesson_number: i32,
-    succeeded_lessons: i32,
-    failed_lessons: i32,
-    wpm_history: Vec<i32>,
-    average_wpm: i32,
-    acc_history: Vec<i32>,
-    average_accuracy: i32,
-    current_lesson_string: String,
-    current_typed_string: String,
-    ngrams: Vec<String>,
-
-    // wpm and acc tracking
-    need_wpm: i32,
-    need_acc: i32,
-    acc_key_hits: i32,
-    acc_key_misses: i32,
-    wpm_start_time: std::time::Instant,
-
-    // emulation
-    use_emulation: bool,
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "count ngrams in your own texts and write a list that can be used with --n.")]
+    Corpus(corpus::CorpusArgs),
+    #[command(about = "list your slowest letter transitions and most missed keys, based on the lesson history.")]
+    Report(report::ReportArgs),
+    #[command(about = "play back a recorded session, like the ones saved next to the history.")]
+    Replay(replay::ReplayArgs),
 }
 
-fn try_get_from_file(path: &str) -> Vec<String> {
-    // check if path is a file
-    if std::path::Path::new(path).is_file() {
-        return ngrams::get_from_file(path.to_string());
-    } else {
-        println!("File not found: {}", path);
+// The flags that shape the lessons, the ones of session_options but the layouts.
+const LESSON_FLAGS: [&str; 15] = [
+    "n", "top", "combi", "rep", "wpm", "acc", "chars", "inject", "time", "progress", "on_fail", "max_retries",
+    "sample", "explore", "seed",
+];
+
+// The options of the session, taken from the program args.
+fn session_options(args: &Args) -> Options {
+    Options {
+        n: args.n.clone(),
+        top: args.top,
+        combi: args.combi,
+        rep: args.rep,
+        wpm: args.wpm,
+        acc: args.acc,
+        chars: args.chars.clone(),
+        inject: args.inject.clone(),
+        time: args.time,
+        progress: args.progress,
+        on_fail: args.on_fail.clone(),
+        max_retries: args.max_retries,
+        sample: args.sample.clone(),
+        explore: args.explore,
+        seed: args.seed,
+        emu_in: args.emu_in.clone(),
+        emu_out: args.emu_out.clone(),
+    }
+}
+
+// Returns the built-in layout with the given name or loads it from a layout file.
+// No layout at all means qwerty.
+fn get_layout(name: &str) -> layout::Layout {
+    if name.is_empty() {
+        return layout::Layout::Qwerty;
+    }
+    if let Some(layout) = layout::from_name(name) {
+        return layout;
+    }
+    if !std::path::Path::new(name).is_file() {
+        println!("Unknown layout: {}. Use one of the supported layouts or a layout file.", name);
         std::process::exit(1);
     }
+    match layout::load(name) {
+        Ok(layout) => layout,
+        Err(err) => {
+            println!("Invalid layout file {}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
-fn get_ngrams(args: &Args) -> Vec<String> {
-    let ngrams = match args.n.as_str() {
-        "2" => ngrams::get_bigrams(),
-        "3" => ngrams::get_trigrams(),
-        "4" => ngrams::get_tetragrams(),
-        "w" => ngrams::get_wordlist(),
-        &_ => try_get_from_file(&args.n),
+// Returns the ghost of the recording at the given path, or of the best recorded session
+// with the given options.
+fn get_ghost(race: &str, options: &Options) -> race::Ghost {
+    let ghost = match race {
+        "best" => match recording::recordings_dir().map(|dir| race::best(&dir, options)) {
+            Some(Ok(Some(ghost))) => Ok(ghost),
+            Some(Err(err)) => Err(format!("Could not read the recordings: {}", err)),
+            _ => Err("There is no recorded session with these options to race yet. Finish a lesson first.".to_string()),
+        },
+        path => recording::load(std::path::Path::new(path))
+            .and_then(race::Ghost::from_recording)
+            .map_err(|err| format!("Could not load recording {}: {}", path, err)),
     };
-    ngrams
+    ghost.unwrap_or_else(|err| {
+        println!("{}", err);
+        std::process::exit(1);
+    })
 }
 
 fn validate_args(args: &Args) -> bool {
-    if args.top < 1 || args.top > 200 {
-        println!("Invalid argument for top. Use a number between 1 and 200.");
+    if let Err(err) = session_options(args).validate() {
+        println!("{}", err);
         return false;
     }
-    if args.combi < 1 || args.combi > 200 {
-        println!("Invalid argument for combi. Use a number between 1 and 200.");
+    if let Err(err) = args.display.validate() {
+        println!("{}", err);
         return false;
     }
-    if args.rep < 1 || args.rep > 200 {
-        println!("Invalid argument for rep. Use a number between 1 and 200.");
-        return false;
-    }
-    if args.wpm < 1 || args.wpm > 200 {
-        println!("Invalid argument for wpm. Use a number between 1 and 200.");
-        return false;
-    }
-    if args.acc < 0 || args.acc > 100 {
-        println!("Invalid argument for acc. Use a number between 0 and 100.");
-        return false;
-    }
-    if args.emu_in != "" && args.emu_out == "" {
-        println!("You need to specify both emu_in and emu_out.");
-        return false;
-    }
-    if args.emu_in == "" && args.emu_out != "" {
-        println!("You need to specify both emu_in and emu_out.");
+    if !args.emu_in.is_empty() && args.emu_out.is_empty() {
+        println!("You need to specify emu_out to emulate a layout.");
         return false;
     }
     true
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    if let Some(command) = &args.command {
+        let result = match command {
+            Command::Corpus(corpus_args) => corpus::run(corpus_args),
+            Command::Report(report_args) => report::run(report_args),
+            Command::Replay(replay_args) => replay::run(replay_args),
+        };
+        if let Err(err) = result {
+            println!("{}", err);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Err(err) = config::apply(&mut args, &matches) {
+        println!("{}", err);
+        std::process::exit(1);
+    }
 
     if !validate_args(&args) {
         std::process::exit(1);
     }
+    // scripted sessions are not practice, they must not end up in the history or the recordings
+    if !args.script.is_empty() {
+        args.nohistory = true;
+    }
+
+    // a race brings the lessons of the ghost, so lesson flags would be replaced without notice.
+    // --race best picks the ghost by them, only its seed is replaced
+    if !args.race.is_empty() {
+        let replaced: &[&str] = match args.race.as_str() {
+            "best" => &["seed"],
+            _ => &LESSON_FLAGS,
+        };
+        if let Some(flag) = replaced.iter().find(|flag| matches.value_source(flag) == Some(ValueSource::CommandLine)) {
+            println!("--{} can not be used with --race {}, the lessons come from the recorded session.", flag.replace('_', "-"), args.race);
+            std::process::exit(1);
+        }
+    }
 
-    // TODO: refactor this into a function
-    let in_layout = match args.emu_in.as_str() {
-        "qwerty" => layout::Layout::Qwerty,
-        "qwertz" => layout::Layout::Qwertz,
-        "azerty" => layout::Layout::Azerty,
-        "dvorak" => layout::Layout::Dvorak,
-        "colemak" => layout::Layout::Colemak,
-        "colemakdh" => layout::Layout::ColemakDH,
-        &_ => layout::Layout::Qwerty,
+    let out_layout = get_layout(&args.emu_out);
+    let keyboards = tui::Keyboards {
+        full: layout::Keyboard::new(&out_layout, args.display.show_ortho, false),
+        compact: layout::Keyboard::new(&out_layout, args.display.show_ortho, true),
     };
-    let out_layout = match args.emu_out.as_str() {
-        "qwerty" => layout::Layout::Qwerty,
-        "qwertz" => layout::Layout::Qwertz,
-        "azerty" => layout::Layout::Azerty,
-        "dvorak" => layout::Layout::Dvorak,
-        "colemak" => layout::Layout::Colemak,
-        "colemakdh" => layout::Layout::ColemakDH,
-        &_ => layout::Layout::Qwerty,
+
+    let mut history = history::History::default();
+    let mut history_path = None;
+    if !args.nohistory {
+        history_path = history::history_path();
+        if let Some(path) = &history_path {
+            match history::load(path) {
+                Ok(loaded) => history = loaded,
+                Err(err) => {
+                    println!("Could not load history file {}: {}", path.display(), err);
+                    println!("Fix or move the file, or pass --nohistory to practice without saving.");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+    // a race has the lessons and options of the ghost, only the layouts are our own
+    let ghost = (!args.race.is_empty()).then(|| get_ghost(&args.race, &session_options(&args)));
+    let options = match &ghost {
+        Some(ghost) => Options {
+            emu_in: args.emu_in.clone(),
+            emu_out: args.emu_out.clone(),
+            ..ghost.recording.options.clone()
+        },
+        None => session_options(&args),
     };
-    let out_layout_string = layout::get_layout_string(&out_layout, args.show_ortho);
-
-    let mut state = AppState {
-        current_lesson_number: 0,
-        succeeded_lessons: 0,
-        failed_lessons: 0,
-        wpm_history: Vec::new(),
-        average_wpm: 0,
-        acc_history: Vec::new(),
-        average_accuracy: 0,
-        current_lesson_string: "".to_string(),
-        current_typed_string: "".to_string(),
-        ngrams: vec![],
-
-        need_wpm: args.wpm,
-        need_acc: args.acc,
-
-        acc_key_hits: 0,
-        acc_key_misses: 0,
-        wpm_start_time: std::time::Instant::now(),
-
-        use_emulation: args.emu_in != "" && args.emu_out != "", // only use emulation if both are set
+    let session = match &ghost {
+        Some(ghost) => Session::with_lessons(options, ghost.recording.lessons.clone(), history, history_path),
+        None => Session::new(options, history, history_path),
+    };
+    let mut session = match session {
+        Ok(session) => session,
+        Err(err) => {
+            println!("{}", err);
+            std::process::exit(1);
+        }
     };
 
-    state.ngrams = get_ngrams(&args);
+    let script = match args.script.as_str() {
+        "" => None,
+        path => match input::ScriptedInput::load(path) {
+            Ok(script) => Some(script),
+            Err(err) => {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+        },
+    };
 
     let mut terminal = tui::init_tui()?;
+    let mut input: Box<dyn InputSource> = match script {
+        Some(script) => Box::new(script),
+        // the kitty keyboard protocol is only needed to emulate a layout
+        None => Box::new(terminal::TerminalInput::new(!args.nokitty && !args.emu_out.is_empty())?),
+    };
 
-    let mut kb_emu = layout::KbEmulator::new(in_layout, out_layout);
+    // with the kitty keyboard protocol, the terminal tells us the physical key on a qwerty
+    // keyboard, so the system layout does not matter. Otherwise we map the typed chars.
+    let in_layout = if input.reports_base_keys() {
+        layout::Layout::Qwerty
+    } else if args.emu_in.is_empty() && !args.emu_out.is_empty() {
+        input.cleanup()?;
+        tui::cleanup_tui()?;
+        match args.script.is_empty() {
+            true => println!("Your terminal does not report layout independent keys (kitty keyboard protocol)."),
+            false => println!("Scripts do not report layout independent keys."),
+        }
+        println!("Please also pass your current layout with --emu-in.");
+        std::process::exit(1);
+    } else {
+        get_layout(&args.emu_in)
+    };
+    let kb_emu = layout::KbEmulator::new(&in_layout, &out_layout);
+    let emulator = (!args.emu_out.is_empty()).then_some(&kb_emu);
 
     let mut cat_iter = cat::cat();
     let mut cat_frame: String = cat_iter.next().expect("cat frame not found").to_string();
@@ -241,13 +432,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     loop {
         let now = std::time::Instant::now();
-        if let Ok(_) = tui::ensure_screen_size(&mut terminal, &args) {
-            tui::render_tui(&state, &mut terminal, &args, &out_layout_string, &cat_frame)?;
+        if tui::ensure_screen_size(&mut terminal).is_ok() {
+            tui::render_tui(&session.snapshot(now), &mut terminal, &args.display, &keyboards, ghost.as_ref(), &cat_frame)?;
         }
-        let should_quit = game::run_game(&args, &mut state, &mut kb_emu)?;
-        if should_quit {
-            break;
+        if let Some(key) = input.poll_key(std::time::Duration::from_millis(16))? {
+            if key.is_quit() {
+                break;
+            }
+            if let Some(key) = key.to_session_key(emulator) {
+                session.handle_key(key, std::time::Instant::now());
+            }
         }
+        session.tick(std::time::Instant::now());
         dt = now.elapsed();
         cat_timer += dt;
         if cat_timer.as_millis() > 160 {
@@ -256,7 +452,45 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    // recordings are saved next to the history, so --nohistory turns them off as well
+    let saved_recording = match recording::recordings_dir() {
+        Some(dir) if !args.nohistory && !session.recording().keys.is_empty() => {
+            let recording = recording::Recording {
+                layout: layout::LayoutFile::of(&out_layout),
+                ..session.recording().clone()
+            };
+            Some(recording::save(&dir, &recording))
+        }
+        _ => None,
+    };
+
+    let summary = summary::Summary::from_session(&session);
+    if let Some(summary) = &summary {
+        // wait for any key, so the summary does not vanish with the key that quit
+        loop {
+            tui::render_summary(&mut terminal, summary)?;
+            if input.poll_key(std::time::Duration::from_millis(100))?.is_some() {
+                break;
+            }
+        }
+    }
+
+    input.cleanup()?;
     tui::cleanup_tui()?;
 
+    if let Some(summary) = summary {
+        for line in summary.lines() {
+            println!("{}", line);
+        }
+    }
+    if let Some(err) = session.save_error() {
+        println!("{}", err);
+    }
+    match saved_recording {
+        Some(Ok(path)) => println!("Replay this session with: ngrrram replay {}", path.display()),
+        Some(Err(err)) => println!("Could not save the recording: {}", err),
+        None => {}
+    }
+
     Ok(())
 }
@@ -5,6 +5,10 @@
 // - https://github.com/KaranAgarwalla/Encryption-Decryption/blob/master/ngrams3.txt
 // - https://github.com/KaranAgarwalla/Encryption-Decryption/blob/master/ngrams4.txt
 
+use std::collections::HashMap;
+
+use crate::chars;
+
 
 // Returns a Vec<String> of the most common bigrams in the English language.
 pub fn get_bigrams() -> Vec<String> {
@@ -98,21 +102,148 @@ pub fn get_wordlist() -> Vec<String> {
     wordlist.iter().map(|s| s.to_string()).collect()
 }
 
-// Returns a Vec<String> of ngrams from a comma separated file.
-// The file must include no spaces between the commas. Line breaks are allowed.
+// Returns the frequencies of a list that is ordered by usage but carries no frequency data,
+// like the built-in lists. They are approximated with the power law frequency ~ 1 / rank^exponent.
+// English letter ngrams roughly follow an exponent of 0.5, words one of 1 (Zipf's law).
+pub fn rank_weights(len: usize, exponent: f64) -> Vec<f64> {
+    (1..=len).map(|rank| 1.0 / (rank as f64).powf(exponent)).collect()
+}
+
+// Returns the ngrams from a file along with their frequency weights.
+// The file is either a comma separated list ordered by usage, or has one ngram per
+// line followed by its weight (any number, e.g. an absolute count), separated by whitespace.
+// The comma separated list must include no spaces between the commas. Line breaks are allowed.
+// Lists without weights are weighted by rank, see `rank_weights`.
 //
 // # Example file content:
 // ```
 // the,and,ing,ion,tio,ent,
 // all,ons
 // ```
-pub fn get_from_file(path: String) -> Vec<String> {
-    let file = std::fs::read_to_string(path).unwrap();
+// or
+// ```
+// the 1776
+// and 1163
+// ```
+pub fn get_from_file(path: String) -> Result<(Vec<String>, Vec<f64>), String> {
+    let file = std::fs::read_to_string(&path).map_err(|err| format!("Could not read {}: {}", path, err))?;
     let mut ngrams: Vec<String> = Vec::new();
-    for line in file.lines() {
-        for ngram in line.split(",") {
+    let mut weights: Vec<f64> = Vec::new();
+    for (line_number, line) in file.lines().enumerate() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        if columns.len() > 1 {
+            let weight = columns[1].parse::<f64>().ok().filter(|w| w.is_finite() && *w >= 0.0).ok_or(format!(
+                "{}:{}: invalid weight {}, expected a number of 0 or more.",
+                path,
+                line_number + 1,
+                columns[1]
+            ))?;
+            ngrams.push(columns[0].to_string());
+            weights.push(weight);
+            continue;
+        }
+        // skip empty entries, e.g. from trailing commas
+        for ngram in line.split(",").filter(|ngram| !ngram.is_empty()) {
             ngrams.push(ngram.to_string());
         }
     }
-    ngrams
+    if weights.is_empty() {
+        weights = rank_weights(ngrams.len(), 1.0);
+    } else if weights.len() != ngrams.len() {
+        return Err(format!("{}: either all or none of the ngrams need a weight.", path));
+    }
+    Ok((ngrams, weights))
+}
+
+// Returns the ngram list for the n option along with the frequency weight of every ngram.
+// Ngrams with characters that can not be typed with the enabled character classes are left out.
+pub fn load(n: &str, char_classes: &[String]) -> Result<(Vec<String>, Vec<f64>), String> {
+    let rank_weighted = |ngrams: Vec<String>, exponent: f64| {
+        let weights = rank_weights(ngrams.len(), exponent);
+        (ngrams, weights)
+    };
+    let (ngrams, weights) = match n {
+        "2" => rank_weighted(get_bigrams(), 0.5),
+        "3" => rank_weighted(get_trigrams(), 0.5),
+        "4" => rank_weighted(get_tetragrams(), 0.5),
+        "w" => rank_weighted(get_wordlist(), 1.0),
+        &_ if !std::path::Path::new(n).is_file() => return Err(format!("File not found: {}", n)),
+        &_ => get_from_file(n.to_string())?,
+    };
+    let (ngrams, weights): (Vec<String>, Vec<f64>) = ngrams
+        .into_iter()
+        .zip(weights)
+        .filter(|(ngram, _)| ngram.chars().all(|c| chars::is_allowed(c, char_classes)))
+        .unzip();
+    if ngrams.is_empty() {
+        return Err(format!("No ngrams of {} can be typed. Enable more character classes with --chars.", n));
+    }
+    Ok((ngrams, weights))
+}
+
+// How to count the ngrams of a text, see `count_ngrams`.
+pub struct CorpusOptions {
+    pub fold_case: bool,
+    pub alphabet: Option<Vec<char>>,
+    pub min_count: usize,
+}
+
+// What to count in a text.
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Chars(usize), // ngrams of this many characters
+    Words,
+}
+
+// Counts the ngrams (or words) in text and returns them with their count,
+// ordered from most to least frequent. Ngrams never span characters outside the
+// alphabet, so they never cross word boundaries.
+pub fn count_ngrams(text: &str, unit: Unit, options: &CorpusOptions) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let in_alphabet = |c: char| match &options.alphabet {
+        Some(alphabet) => alphabet.contains(&c),
+        None => c.is_alphabetic(),
+    };
+
+    let mut word: Vec<char> = Vec::new();
+    let mut count_word = |word: &[char]| match unit {
+        Unit::Words => {
+            if !word.is_empty() {
+                *counts.entry(word.iter().collect()).or_default() += 1;
+            }
+        }
+        Unit::Chars(n) => {
+            for window in word.windows(n) {
+                *counts.entry(window.iter().collect()).or_default() += 1;
+            }
+        }
+    };
+
+    for c in text.chars() {
+        let c = if options.fold_case { c.to_lowercase().next().unwrap_or(c) } else { c };
+        if in_alphabet(c) {
+            word.push(c);
+        } else {
+            count_word(&word);
+            word.clear();
+        }
+    }
+    count_word(&word);
+
+    let mut counted: Vec<(String, usize)> = counts
+        .into_iter()
+        .filter(|(_, count)| *count >= options.min_count)
+        .collect();
+    // ties are ordered alphabetically so the output is stable
+    counted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counted
+}
+
+// Formats counted ngrams as a list that can be loaded with --n, one ngram per line
+// followed by its count, so the list can also be sampled by frequency.
+pub fn format_list(counted: &[(String, usize)]) -> String {
+    counted
+        .iter()
+        .map(|(ngram, count)| format!("{} {}\n", ngram, count))
+        .collect()
 }
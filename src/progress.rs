@@ -0,0 +1,66 @@
+use crate::ngrams;
+use crate::session::Session;
+
+// How much the ngram pool is widened per tier, and where that stops.
+const TOP_STEP: i32 = 25;
+const MAX_TOP: i32 = 200;
+// How many different ngrams a lesson combines at most before moving on to the next list.
+const MAX_COMBI: i32 = 4;
+// The built-in lists, in the order they are progressed through.
+const LISTS: [&str; 4] = ["2", "3", "4", "w"];
+
+// Counts a finished lesson towards the next tier and advances once enough
+// consecutive lessons were passed. Does nothing unless progress is set.
+pub fn record_lesson(session: &mut Session, success: bool) {
+    if session.options.progress == 0 {
+        return;
+    }
+    if !success {
+        session.consecutive_passes = 0;
+        return;
+    }
+    session.consecutive_passes += 1;
+    if session.consecutive_passes >= session.options.progress {
+        session.consecutive_passes = 0;
+        advance(session);
+    }
+}
+
+// Moves to the next tier: first widen the pool of top ngrams, then combine more
+// ngrams per lesson, then start over with the next built-in list.
+fn advance(session: &mut Session) {
+    let max_top = MAX_TOP.min(session.ngrams.len() as i32);
+    if session.top < max_top {
+        session.top = (session.top + TOP_STEP).min(max_top);
+        return;
+    }
+    if session.combi < MAX_COMBI.max(session.options.combi) {
+        session.combi += 1;
+        return;
+    }
+    // custom files have no next list, so they stay at their last tier
+    if let Some(index) = LISTS.iter().position(|list| *list == session.n) {
+        if let Some(next) = LISTS.get(index + 1) {
+            // the built-in lists always have ngrams made of letters only, so this never fails
+            if let Ok((ngrams, weights)) = ngrams::load(next, &session.char_classes) {
+                session.n = next.to_string();
+                session.ngrams = ngrams;
+                session.ngram_weights = weights;
+                session.top = session.options.top;
+                session.combi = session.options.combi;
+            }
+        }
+    }
+}
+
+// Returns a short description of the current tier, like "tri 75x3 (1/3)".
+pub fn tier_label(session: &Session) -> String {
+    let list = match session.n.as_str() {
+        "2" => "bi",
+        "3" => "tri",
+        "4" => "tetra",
+        "w" => "words",
+        &_ => "file",
+    };
+    format!("{} {}x{} ({}/{})", list, session.top, session.combi, session.consecutive_passes, session.options.progress)
+}
@@ -0,0 +1,118 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::history::History;
+use crate::recording::{self, Recording};
+use crate::session::{Key, Options, Session, Snapshot};
+
+// A recorded session to race against. Its keys are played back headless once, to know
+// how far into every lesson it was at any time.
+pub struct Ghost {
+    pub recording: Recording,
+    // for every lesson, how many chars were typed how many milliseconds after its first key.
+    // Of repeated lessons, only the last attempt is kept.
+    lessons: Vec<Vec<(u64, usize)>>,
+}
+
+impl Ghost {
+    pub fn from_recording(recording: Recording) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut session = Session::with_lessons(recording.options.clone(), recording.lessons.clone(), History::default(), None)?;
+
+        let start = Instant::now();
+        let mut lessons: Vec<Vec<(u64, usize)>> = Vec::new();
+        let mut attempt: Vec<(u64, usize)> = Vec::new();
+        let mut attempt_start = 0;
+        for (millis, key) in &recording.keys {
+            let at = start + Duration::from_millis(*millis);
+            // a timed test can run out between two keys
+            let (lesson_number, finished, _, _) = progress(&session.snapshot(at));
+            if progress(&session.tick(at)).1 > finished {
+                store_attempt(&mut lessons, lesson_number, std::mem::take(&mut attempt));
+            }
+
+            let (lesson_number, finished, typed, lesson_len) = progress(&session.snapshot(at));
+            // like the wpm timer, the attempt starts over with the first char
+            if typed == 0 && matches!(key, Key::Char(_)) {
+                attempt.clear();
+                attempt_start = *millis;
+            }
+            let (_, finished_after, typed_after, _) = progress(&session.handle_key(*key, at));
+            if finished_after > finished {
+                // the typed text is gone with the next lesson, but it was the whole lesson
+                attempt.push((millis - attempt_start, lesson_len));
+                store_attempt(&mut lessons, lesson_number, std::mem::take(&mut attempt));
+            } else {
+                attempt.push((millis - attempt_start, typed_after));
+            }
+        }
+
+        Ok(Ghost { recording, lessons })
+    }
+
+    // How many chars of the lesson the ghost had typed at the time since the first key.
+    // None if the ghost never got to the lesson.
+    pub fn position(&self, lesson_number: i32, elapsed: Duration) -> Option<usize> {
+        let attempt = self.lessons.get(lesson_number as usize - 1).filter(|a| !a.is_empty())?;
+        let millis = elapsed.as_millis() as u64;
+        Some(attempt.iter().take_while(|(at, _)| *at <= millis).last().map_or(0, |(_, typed)| *typed))
+    }
+
+    // How many milliseconds the ghost needed longer than typed_at to get to the typed
+    // char, negative if it was faster. None if the ghost never got there.
+    pub fn ahead_millis(&self, lesson_number: i32, typed: usize, typed_at: Duration) -> Option<i64> {
+        let attempt = self.lessons.get(lesson_number as usize - 1)?;
+        let (ghost_at, _) = attempt.iter().find(|(_, ghost_typed)| *ghost_typed >= typed)?;
+        Some(*ghost_at as i64 - typed_at.as_millis() as i64)
+    }
+}
+
+// The lesson number, the number of finished lessons, the typed chars and the lesson length.
+fn progress(snapshot: &Snapshot) -> (i32, i32, usize, usize) {
+    (snapshot.lesson_number, snapshot.passed + snapshot.failed, snapshot.typed.chars().count(), snapshot.lesson.chars().count())
+}
+
+fn store_attempt(lessons: &mut Vec<Vec<(u64, usize)>>, lesson_number: i32, attempt: Vec<(u64, usize)>) {
+    let index = lesson_number as usize - 1;
+    if lessons.len() <= index {
+        lessons.resize(index + 1, Vec::new());
+    }
+    lessons[index] = attempt;
+}
+
+// Returns the ghost of the recording in dir with the highest average wpm among those
+// practiced with the same options, apart from the seed and the layouts. None if there is
+// no such recording with a finished lesson. Recordings that can not be read are skipped.
+pub fn best(dir: &Path, options: &Options) -> Result<Option<Ghost>, Box<dyn std::error::Error>> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut best: Option<(i32, Recording)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Ok(recording) = recording::load(&path) else {
+            continue;
+        };
+        let Some(average_wpm) = recording.average_wpm else {
+            continue;
+        };
+        if !same_lessons(&recording.options, options) {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(best_wpm, _)| average_wpm > *best_wpm) {
+            best = Some((average_wpm, recording));
+        }
+    }
+    // only the best one is played back
+    best.map(|(_, recording)| Ghost::from_recording(recording)).transpose()
+}
+
+// Whether lessons of both options are alike, whatever seed and layouts they use.
+fn same_lessons(a: &Options, b: &Options) -> bool {
+    let plain = |options: &Options| Options {
+        seed: None,
+        emu_in: String::new(),
+        emu_out: String::new(),
+        ..options.clone()
+    };
+    plain(a) == plain(b)
+}
@@ -0,0 +1,77 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::LayoutFile;
+use crate::session::{Key, Options};
+
+// Bump this whenever the format of recordings changes in an incompatible way.
+pub const RECORDING_VERSION: u32 = 1;
+
+// Everything needed to play a session back: the options it was practiced with, including
+// the seed and the emulated layout, every lesson and every key with the time it was typed at.
+// An emulated layout file is kept as its rows, so the recording plays back on machines
+// without the file.
+//
+// # Example file content:
+// ```
+// {"version":1,"timestamp":1760000000,"options":{...},"average_wpm":48,"lessons":["th he th he "],
+//  "keys":[[0,"t"],[153,"h"],[301," "],[420,"backspace"]]}
+// ```
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Recording {
+    pub version: u32,
+    pub timestamp: u64, // seconds since the unix epoch
+    pub options: Options, // with the seed the session actually used
+    pub average_wpm: Option<i32>, // of the finished lessons, None if there are none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayoutFile>, // the rows of options.emu_out, if it is a layout file
+    pub lessons: Vec<String>, // the lessons in the order they came up, repeated ones only once
+    pub keys: Vec<(u64, Key)>, // milliseconds since the first key of the session and the key
+}
+
+// Returns the directory recordings are saved to inside the XDG data directory,
+// or None if there is no data directory on this platform.
+pub fn recordings_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("ngrrram").join("recordings"))
+}
+
+pub fn load(path: &Path) -> Result<Recording, Box<dyn std::error::Error>> {
+    let file = std::fs::read_to_string(path)?;
+    let recording: Recording = serde_json::from_str(&file)?;
+    if recording.version > RECORDING_VERSION {
+        return Err(format!(
+            "{} was written by a newer version of ngrrram (recording version {}, supported up to {}).",
+            path.display(),
+            recording.version,
+            RECORDING_VERSION
+        )
+        .into());
+    }
+    Ok(recording)
+}
+
+// Saves the recording to a new file in dir, named after the time the session started,
+// and returns its path. Sessions started in the same second get a suffix, like 1760000000-1.json.
+pub fn save(dir: &Path, recording: &Recording) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dir)?;
+    let content = serde_json::to_string(recording)?;
+    let mut suffix = 0;
+    loop {
+        let name = match suffix {
+            0 => format!("{}.json", recording.timestamp),
+            suffix => format!("{}-{}.json", recording.timestamp, suffix),
+        };
+        let path = dir.join(name);
+        // create_new, so an existing recording is never overwritten
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(path);
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => suffix += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use ngrrram::input::InputSource;
+use ngrrram::{history, layout, recording, Session};
+
+use crate::terminal::TerminalInput;
+use crate::{cat, tui};
+
+#[derive(clap::Args)]
+pub struct ReplayArgs {
+    #[arg(value_name = "file", help = "the recording to play back.")]
+    file: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "1",
+        value_name = "factor",
+        help = "play back faster (2) or slower (0.5) than the session was typed."
+    )]
+    speed: f64,
+
+    #[command(flatten)]
+    display: tui::DisplayArgs,
+}
+
+// Runs the replay subcommand, playing a recorded session back in the tui until <esc>.
+pub fn run(args: &ReplayArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if !(args.speed > 0.0 && args.speed.is_finite()) {
+        return Err("Invalid argument for speed. Use a factor above 0, like 2 for double speed.".into());
+    }
+    args.display.validate()?;
+    let recording = recording::load(Path::new(&args.file))
+        .map_err(|err| format!("Could not load recording {}: {}", args.file, err))?;
+
+    // a layout file is kept in the recording, so it plays back without the file
+    let out_layout = match &recording.layout {
+        Some(rows) => layout::from_file(rows).map_err(|err| format!("Invalid layout in recording {}: {}", args.file, err))?,
+        None => crate::get_layout(&recording.options.emu_out),
+    };
+    let keyboards = tui::Keyboards {
+        full: layout::Keyboard::new(&out_layout, args.display.show_ortho, false),
+        compact: layout::Keyboard::new(&out_layout, args.display.show_ortho, true),
+    };
+
+    // the recorded keys went through emulation already, so they go to the session as they are.
+    // The lessons are planned, since the seed alone does not bring back weak sampling, and
+    // so the ngram list of the recording is not needed.
+    let mut session = Session::with_lessons(recording.options.clone(), recording.lessons.clone(), history::History::default(), None)?;
+
+    let mut terminal = tui::init_tui()?;
+    let mut input = TerminalInput::new(false)?;
+
+    let mut cat_iter = cat::cat();
+    let mut cat_frame: String = cat_iter.next().expect("cat frame not found").to_string();
+    let mut cat_time = Instant::now();
+
+    let start = Instant::now();
+    let mut keys = recording.keys.iter().peekable();
+    loop {
+        // the session gets the recorded times, only the playback is sped up or slowed down
+        let now = start + start.elapsed().mul_f64(args.speed);
+        while let Some((millis, key)) = keys.next_if(|(millis, _)| start + Duration::from_millis(*millis) <= now) {
+            session.handle_key(*key, start + Duration::from_millis(*millis));
+        }
+        session.tick(now);
+        if cat_time.elapsed().as_millis() > 160 {
+            cat_time = Instant::now();
+            cat_frame = cat_iter.next().expect("cat frame not found").to_string();
+        }
+
+        if tui::ensure_screen_size(&mut terminal).is_ok() {
+            tui::render_tui(&session.snapshot(now), &mut terminal, &args.display, &keyboards, None, &cat_frame)?;
+        }
+        if let Some(key) = input.poll_key(Duration::from_millis(16))? {
+            if key.is_quit() {
+                break;
+            }
+        }
+    }
+
+    input.cleanup()?;
+    tui::cleanup_tui()?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use ngrrram::{history, stats};
+
+#[derive(clap::Args)]
+pub struct ReportArgs {
+    #[arg(
+        long,
+        default_value = "",
+        value_name = "layout",
+        help = "report on lessons practiced while emulating this layout."
+    )]
+    emu_out: String,
+
+    #[arg(
+        short,
+        long,
+        default_value = "20",
+        value_name = "number",
+        help = "how many transitions to list."
+    )]
+    count: usize,
+
+    #[arg(
+        long,
+        default_value = "5",
+        value_name = "number",
+        help = "leave out transitions timed less often than this."
+    )]
+    min_samples: u32,
+}
+
+// Runs the report subcommand, printing the slowest letter transitions and the keys with
+// the highest error rate from the history.
+pub fn run(args: &ReportArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let path = history::history_path().ok_or("There is no data directory on this system.")?;
+    let history = history::load(&path)?;
+
+    let transitions: Vec<(String, f64, u32)> = stats::slowest_transitions(&history, &args.emu_out)
+        .into_iter()
+        .filter(|(_, _, count)| *count >= args.min_samples)
+        .take(args.count)
+        .collect();
+    if transitions.is_empty() {
+        println!("Not enough data yet. Practice some more, or lower --min-samples.");
+        return Ok(());
+    }
+
+    println!("Slowest letter transitions:");
+    for (pair, millis, count) in transitions {
+        let mut chars = pair.chars();
+        let (from, to) = (chars.next().unwrap_or(' '), chars.next().unwrap_or(' '));
+        println!("  {} → {}  {:5.0} ms  ({} samples)", from, to, millis, count);
+    }
+
+    let mut keys: Vec<(char, stats::KeyStats)> = stats::keys_from_history(&history, &args.emu_out)
+        .into_iter()
+        .filter(|(key, stats)| *key != ' ' && stats.presses >= args.min_samples && stats.misses > 0)
+        .collect();
+    keys.sort_by(|a, b| b.1.error_rate().total_cmp(&a.1.error_rate()).then_with(|| a.0.cmp(&b.0)));
+    if !keys.is_empty() {
+        println!();
+        println!("Most missed keys:");
+        for (key, stats) in keys.into_iter().take(args.count) {
+            println!(
+                "  {}  {:4.1}% missed  ({} of {}, typed by mistake {} times)",
+                key,
+                stats.error_rate() * 100.0,
+                stats.misses,
+                stats.presses + stats.misses,
+                stats.wrong
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,638 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::recording::{Recording, RECORDING_VERSION};
+use crate::{chars, history, ngrams, progress, stats};
+
+// How many ngrams are added to the stream of a timed test at once, and how many
+// characters ahead of the cursor the stream has to reach.
+const STREAM_CHUNK: i32 = 50;
+const STREAM_AHEAD: usize = 200;
+
+// A key event as the session understands it. Frontends translate their input into
+// these, after layout emulation.
+// In recordings a key is the character itself, or backspace or delete-word.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Key {
+    Char(char),
+    Backspace,
+    DeleteWord, // deletes back to the last space
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        match key {
+            Key::Char(c) => c.to_string(),
+            Key::Backspace => "backspace".to_string(),
+            Key::DeleteWord => "delete-word".to_string(),
+        }
+    }
+}
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        let mut chars = name.chars();
+        match (name.as_str(), chars.next(), chars.next()) {
+            ("backspace", _, _) => Ok(Key::Backspace),
+            ("delete-word", _, _) => Ok(Key::DeleteWord),
+            (_, Some(c), None) => Ok(Key::Char(c)),
+            _ => Err(format!("unknown key {}", name)),
+        }
+    }
+}
+
+// The settings that shape the lessons of a session and decide whether they pass,
+// named like the command line flags.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Options {
+    pub n: String,
+    pub top: i32,
+    pub combi: i32,
+    pub rep: i32,
+    pub wpm: i32,
+    pub acc: i32,
+    pub chars: Vec<String>,
+    pub inject: Vec<String>,
+    pub time: i32,
+    pub progress: i32,
+    pub on_fail: String,
+    pub max_retries: i32,
+    pub sample: String,
+    pub explore: i32,
+    pub seed: Option<u64>, // a random one if None
+    // the layouts only label the results, emulation is up to the frontend
+    pub emu_in: String,
+    pub emu_out: String,
+}
+
+impl Options {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.top < 1 || self.top > 200 {
+            return Err("Invalid argument for top. Use a number between 1 and 200.".to_string());
+        }
+        if self.combi < 1 || self.combi > 200 {
+            return Err("Invalid argument for combi. Use a number between 1 and 200.".to_string());
+        }
+        if self.rep < 1 || self.rep > 200 {
+            return Err("Invalid argument for rep. Use a number between 1 and 200.".to_string());
+        }
+        if self.wpm < 1 || self.wpm > 200 {
+            return Err("Invalid argument for wpm. Use a number between 1 and 200.".to_string());
+        }
+        if self.acc < 0 || self.acc > 100 {
+            return Err("Invalid argument for acc. Use a number between 0 and 100.".to_string());
+        }
+        if let Some(class) = self.chars.iter().find(|c| !chars::CLASSES.contains(&c.as_str())) {
+            return Err(format!("Invalid argument for chars: {}. Use {}.", class, chars::CLASSES.join(", ")));
+        }
+        if let Some(mode) = self.inject.iter().find(|m| !chars::INJECT_MODES.contains(&m.as_str())) {
+            return Err(format!("Invalid argument for inject: {}. Use {}.", mode, chars::INJECT_MODES.join(", ")));
+        }
+        if self.time < 0 || self.time > 600 {
+            return Err("Invalid argument for time. Use a number of seconds between 1 and 600, like 15, 30, 60 or 120, or 0 for lessons.".to_string());
+        }
+        if self.progress < 0 {
+            return Err("Invalid argument for progress. Use a number of 0 or more.".to_string());
+        }
+        if self.on_fail != "new" && self.on_fail != "repeat" {
+            return Err("Invalid argument for on-fail. Use new or repeat.".to_string());
+        }
+        if self.max_retries < 0 {
+            return Err("Invalid argument for max-retries. Use a number of 0 or more.".to_string());
+        }
+        if self.sample != "uniform" && self.sample != "freq" && self.sample != "weak" {
+            return Err("Invalid argument for sample. Use uniform, freq or weak.".to_string());
+        }
+        if self.explore < 0 || self.explore > 100 {
+            return Err("Invalid argument for explore. Use a number between 0 and 100.".to_string());
+        }
+        Ok(())
+    }
+}
+
+// A practice session: the current lesson, what was typed so far and the results of the
+// finished lessons. It knows nothing about terminals, it is driven by key events and
+// the time they happened at, and shows itself through snapshots.
+pub struct Session {
+    pub(crate) options: Options,
+
+    pub(crate) current_lesson_number: i32,
+    pub(crate) current_lesson_retries: i32,
+    pub(crate) succeeded_lessons: i32,
+    pub(crate) failed_lessons: i32,
+    pub(crate) wpm_history: Vec<i32>,
+    pub(crate) average_wpm: i32,
+    pub(crate) acc_history: Vec<i32>,
+    pub(crate) average_accuracy: i32,
+    pub(crate) current_lesson_string: String,
+    pub(crate) current_typed_string: String,
+    pub(crate) current_lesson_ngrams: Vec<Option<String>>,
+
+    // the current tier, starts at the options and moves on with progress
+    pub(crate) n: String,
+    pub(crate) top: i32,
+    pub(crate) combi: i32,
+    pub(crate) consecutive_passes: i32,
+
+    pub(crate) ngrams: Vec<String>,
+    pub(crate) ngram_weights: Vec<f64>, // relative frequency of every ngram, same order as ngrams
+    pub(crate) ngram_stats: HashMap<String, stats::NgramStats>,
+    pub(crate) key_stats: HashMap<char, stats::KeyStats>,
+
+    // wpm and acc tracking
+    pub(crate) acc_key_hits: i32,
+    pub(crate) acc_key_misses: i32,
+    pub(crate) wpm_start_time: Instant,
+    pub(crate) test_started: Option<Instant>, // the first key of a timed test, backspacing does not undo it
+    pub(crate) last_raw_wpm: Option<i32>, // of the last timed test
+
+    // per character tracking of the current lesson, used for the ngram stats
+    pub(crate) key_times: Vec<Instant>,
+    pub(crate) position_misses: Vec<u32>,
+
+    // chars typed instead of the expected ones in the current lesson
+    pub(crate) wrong_keys: HashMap<char, u32>,
+    pub(crate) last_miss: Option<(char, Instant)>, // the wrong char typed last
+
+    // letters and space can always be typed, these classes can be enabled in addition
+    pub(crate) char_classes: Vec<String>,
+
+    // lessons are generated from a seeded rng, so the same seed and options give the same lessons.
+    // Unlike StdRng, ChaCha8 is a fixed algorithm, so they stay the same across versions and platforms
+    pub(crate) seed: u64,
+    rng: ChaCha8Rng,
+    planned_lessons: VecDeque<String>, // lessons to use instead of generating them
+
+    // every lesson and key of the session, to play it back
+    recording: Recording,
+    first_key_time: Option<Instant>, // keys are recorded relative to it
+
+    // persistence, history_path is None if saving is disabled
+    pub(crate) history: history::History,
+    pub(crate) history_path: Option<PathBuf>,
+    save_error: Option<String>,
+    pub(crate) session_start: usize, // index of the first lesson of this session in history.lessons
+}
+
+// What a frontend needs to show the session at a point in time.
+pub struct Snapshot<'a> {
+    pub lesson: &'a str,
+    pub typed: &'a str,
+    pub lesson_number: i32,
+    pub retries: i32, // how often the current lesson was repeated
+    pub passed: i32,
+    pub failed: i32,
+    pub need_wpm: i32,
+    pub need_acc: i32,
+    pub average_wpm: i32,
+    pub average_acc: i32,
+    pub wpm_history: &'a [i32],
+    pub acc_history: &'a [i32],
+    pub last_raw_wpm: Option<i32>,
+    pub time_left: Option<Duration>, // only in timed tests
+    pub elapsed: Option<Duration>, // since the first key of the lesson, None before it
+    pub typed_at: Option<Duration>, // when the last char was typed, since the first key of the lesson
+    pub last_miss: Option<(char, Duration)>, // the wrong char typed last and how long ago
+    pub tier: Option<String>, // only with progress
+    pub key_stats: &'a HashMap<char, stats::KeyStats>,
+}
+
+impl Session {
+    // Starts a session with the first lesson. Results are added to history, and every
+    // lesson is appended to the history file at history_path unless it is None.
+    pub fn new(options: Options, history: history::History, history_path: Option<PathBuf>) -> Result<Self, String> {
+        Session::start(options, VecDeque::new(), history, history_path)
+    }
+
+    // Starts a session with these lessons instead of generated ones, like for a replay or a
+    // race. The ngram list is only needed once the lessons are used up, so a missing one is
+    // no error. Without it, the last lesson comes again.
+    pub fn with_lessons(options: Options, lessons: Vec<String>, history: history::History, history_path: Option<PathBuf>) -> Result<Self, String> {
+        Session::start(options, lessons.into(), history, history_path)
+    }
+
+    fn start(options: Options, planned_lessons: VecDeque<String>, history: history::History, history_path: Option<PathBuf>) -> Result<Self, String> {
+        options.validate()?;
+        let char_classes = chars::enabled_classes(&options.chars, &options.inject);
+        let (ngrams, ngram_weights) = match ngrams::load(&options.n, &char_classes) {
+            Err(_) if !planned_lessons.is_empty() => (vec![], vec![]),
+            loaded => loaded?,
+        };
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let recording = Recording {
+            version: RECORDING_VERSION,
+            timestamp: history::now_timestamp(),
+            options: Options { seed: Some(seed), ..options.clone() },
+            average_wpm: None,
+            layout: None,
+            lessons: vec![],
+            keys: vec![],
+        };
+
+        let mut session = Session {
+            current_lesson_number: 1,
+            current_lesson_retries: 0,
+            succeeded_lessons: 0,
+            failed_lessons: 0,
+            wpm_history: Vec::new(),
+            average_wpm: 0,
+            acc_history: Vec::new(),
+            average_accuracy: 0,
+            current_lesson_string: "".to_string(),
+            current_typed_string: "".to_string(),
+            current_lesson_ngrams: vec![],
+
+            n: options.n.clone(),
+            top: options.top,
+            combi: options.combi,
+            consecutive_passes: 0,
+
+            ngrams,
+            ngram_weights,
+            ngram_stats: stats::from_history(&history, &options.emu_out),
+            key_stats: stats::keys_from_history(&history, &options.emu_out),
+
+            acc_key_hits: 0,
+            acc_key_misses: 0,
+            wpm_start_time: Instant::now(),
+            test_started: None,
+            last_raw_wpm: None,
+
+            key_times: vec![],
+            position_misses: vec![],
+
+            wrong_keys: HashMap::new(),
+            last_miss: None,
+
+            char_classes,
+
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            planned_lessons,
+
+            recording,
+            first_key_time: None,
+
+            session_start: history.lessons.len(),
+            history,
+            history_path,
+            save_error: None,
+            options,
+        };
+        session.next_lesson();
+        Ok(session)
+    }
+
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn history(&self) -> &history::History {
+        &self.history
+    }
+
+    // The seed the lessons are generated from, to practice them again with the seed option.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Why the history could not be saved, if it could not. Lessons after the error are
+    // not saved.
+    pub fn save_error(&self) -> Option<&str> {
+        self.save_error.as_deref()
+    }
+
+    // The session so far, with the lessons and keys up to now.
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    // Makes these the next lessons instead of generating them, starting with the current
+    // one if nothing was typed yet. Once they are used up, lessons are generated again.
+    pub fn plan_lessons(&mut self, lessons: Vec<String>) {
+        self.planned_lessons = lessons.into();
+        if self.current_typed_string.is_empty() {
+            self.recording.lessons.pop();
+            self.next_lesson();
+        }
+    }
+
+    // The state of the session at the given time.
+    pub fn snapshot(&self, at: Instant) -> Snapshot<'_> {
+        let time_left = match (self.options.time, self.test_started) {
+            (0, _) => None,
+            // the clock of a timed test only starts with the first key
+            (time, None) => Some(Duration::from_secs(time as u64)),
+            (time, Some(started)) => Some(Duration::from_secs(time as u64).saturating_sub(at.saturating_duration_since(started))),
+        };
+        Snapshot {
+            lesson: &self.current_lesson_string,
+            typed: &self.current_typed_string,
+            lesson_number: self.current_lesson_number,
+            retries: self.current_lesson_retries,
+            passed: self.succeeded_lessons,
+            failed: self.failed_lessons,
+            need_wpm: self.options.wpm,
+            need_acc: self.options.acc,
+            average_wpm: self.average_wpm,
+            average_acc: self.average_accuracy,
+            wpm_history: &self.wpm_history,
+            acc_history: &self.acc_history,
+            last_raw_wpm: self.last_raw_wpm,
+            time_left,
+            elapsed: (!self.current_typed_string.is_empty()).then(|| at.saturating_duration_since(self.wpm_start_time)),
+            typed_at: self.key_times.last().map(|time| time.saturating_duration_since(self.wpm_start_time)),
+            last_miss: self.last_miss.map(|(c, time)| (c, at.saturating_duration_since(time))),
+            tier: (self.options.progress > 0).then(|| progress::tier_label(self)),
+            key_stats: &self.key_stats,
+        }
+    }
+
+    // Applies a key typed at the given time. Finishing the lesson saves its result.
+    pub fn handle_key(&mut self, key: Key, at: Instant) -> Snapshot<'_> {
+        let first_key_time = *self.first_key_time.get_or_insert(at);
+        self.recording.keys.push((at.saturating_duration_since(first_key_time).as_millis() as u64, key));
+
+        match key {
+            Key::DeleteWord => {
+                if !self.current_typed_string.is_empty() {
+                    // clear until last space, skipping the most recent char, so we can delete a word
+                    // even if we're currently on a space following a word.
+                    // Byte indices, since the typed text can hold chars like ü
+                    let (last_char_index, _) = self.current_typed_string.char_indices().next_back().unwrap();
+                    let last_space_index = self.current_typed_string[..last_char_index]
+                        .char_indices()
+                        .rev()
+                        .find(|(_, c)| *c == ' ')
+                        .map_or(0, |(i, c)| i + c.len_utf8());
+                    self.current_typed_string.truncate(last_space_index);
+                    self.key_times.truncate(self.current_typed_string.chars().count());
+                }
+            }
+            Key::Backspace => {
+                if !self.current_typed_string.is_empty() {
+                    self.current_typed_string.pop();
+                    self.key_times.pop();
+                }
+            }
+            // check if its a letter or of an enabled character class else skip
+            Key::Char(c) if chars::is_allowed(c, &self.char_classes)
+                && self.current_typed_string.chars().count() < self.current_lesson_string.chars().count() => {
+                if self.options.time > 0 {
+                    // the clock of a timed test starts once, with the first key of the stream
+                    if self.test_started.is_none() {
+                        self.test_started = Some(at);
+                        self.wpm_start_time = at;
+                    }
+                } else if self.current_typed_string.is_empty() {
+                    // if this is the first char of the lesson, start the timer
+                    self.wpm_start_time = at;
+                }
+                self.current_typed_string.push(c);
+                self.key_times.push(at);
+                self.acc_key_hits += 1;
+                // check if the last typed char is correct, if not increment misses
+                let pos = self.key_times.len() - 1;
+                if let Some(lesson_char) = self.current_lesson_string.chars().nth(pos) {
+                    if lesson_char != c {
+                        self.acc_key_misses += 1;
+                        self.position_misses[pos] += 1;
+                        *self.wrong_keys.entry(c).or_default() += 1;
+                        self.last_miss = Some((c, at));
+                    }
+                }
+            }
+            Key::Char(_) => {}
+        }
+        self.tick(at)
+    }
+
+    // Lets time pass without a key, which ends a timed test once the time is up.
+    pub fn tick(&mut self, at: Instant) -> Snapshot<'_> {
+        // A TIMED TEST ENDS WHEN THE TIME IS UP, NO MATTER WHERE WE ARE IN THE STREAM
+        let time_is_up = self.options.time > 0
+            && self.test_started.is_some_and(|started| at.saturating_duration_since(started).as_secs() >= self.options.time as u64);
+        if time_is_up {
+            // only what was typed counts towards the result
+            let typed_chars = self.current_typed_string.chars().count();
+            self.current_lesson_string = self.current_lesson_string.chars().take(typed_chars).collect();
+        } else if self.options.time > 0 {
+            self.extend_stream();
+        }
+
+        // CHECK IF LESSON IS SUCCESSFUL, GENERATE NEW LESSON
+        if self.current_lesson_string == self.current_typed_string || time_is_up {
+            let elapsed = match time_is_up {
+                true => Duration::from_secs(self.options.time as u64),
+                false => at.saturating_duration_since(self.wpm_start_time),
+            };
+            let repeat_lesson = self.finish_lesson(elapsed);
+
+            self.current_typed_string.clear();
+            self.test_started = None;
+            if repeat_lesson {
+                self.current_lesson_retries += 1;
+            } else {
+                self.current_lesson_number += 1;
+                self.current_lesson_retries = 0;
+                self.next_lesson();
+            }
+            self.key_times.clear();
+            self.wrong_keys.clear();
+            self.position_misses = vec![0; self.current_lesson_string.chars().count()];
+        }
+
+        self.snapshot(at)
+    }
+
+    // Measures the finished lesson, records it in the history and returns whether
+    // the lesson has to be repeated.
+    fn finish_lesson(&mut self, elapsed: Duration) -> bool {
+        // Calculations are done like described here:
+        // https://www.typetolearn.app/knowledge-base/how-words-per-minute-and-accuracy-are-calculated/
+        let elapsed_mins = elapsed.as_secs_f64() / 60.0;
+        let (wpm, raw_wpm) = if self.options.time > 0 {
+            // like common typing tests, spaces count and net wpm only counts correct chars,
+            // raw wpm every keystroke
+            let correct = self.current_lesson_string.chars()
+                .zip(self.current_typed_string.chars())
+                .filter(|(expected, typed)| expected == typed)
+                .count();
+            (correct as f64 / 5.0 / elapsed_mins, Some((self.acc_key_hits as f64 / 5.0 / elapsed_mins) as i32))
+        } else {
+            // we count every 5 chars as a word, but not including spaces since that would make smaller ngrams easier.
+            ((self.current_typed_string.replace(" ", "").chars().count() as f64 / 5.0) / elapsed_mins, None)
+        };
+        self.last_raw_wpm = raw_wpm;
+        let acc = match self.options.time {
+            0 => (self.acc_key_hits as f64 / (self.acc_key_hits + self.acc_key_misses) as f64) * 100.0,
+            // hits count every keystroke, the wrong ones too, so the share of right keystrokes is
+            _ => ((self.acc_key_hits - self.acc_key_misses) as f64 / self.acc_key_hits as f64) * 100.0,
+        };
+        self.acc_key_hits = 0;
+        self.acc_key_misses = 0;
+
+        // rounding to integers
+        self.wpm_history.push(wpm as i32);
+        self.acc_history.push(acc as i32);
+
+        // calculate averages so far
+        self.average_wpm = self.wpm_history.iter().sum::<i32>() / self.wpm_history.len() as i32;
+        self.average_accuracy = self.acc_history.iter().sum::<i32>() / self.acc_history.len() as i32;
+        self.recording.average_wpm = Some(self.average_wpm);
+
+        let success = wpm as i32 >= self.options.wpm && acc as i32 >= self.options.acc;
+        if success {
+            self.succeeded_lessons += 1;
+        } else {
+            self.failed_lessons += 1;
+        }
+
+        let ngram_results = stats::measure_lesson(
+            &self.current_lesson_string,
+            &self.current_lesson_ngrams,
+            &self.key_times,
+            &self.position_misses,
+        );
+        for result in &ngram_results {
+            self.ngram_stats.entry(result.ngram.clone()).or_default().add(result);
+        }
+        let key_results = stats::measure_keys(
+            &self.current_lesson_string,
+            &self.key_times,
+            &self.position_misses,
+            &self.wrong_keys,
+        );
+        for result in &key_results {
+            self.key_stats.entry(result.key).or_default().add(result);
+        }
+        let transitions = stats::measure_transitions(
+            &self.current_lesson_string,
+            &self.key_times,
+            &self.position_misses,
+        );
+
+        self.history.lessons.push(history::LessonRecord {
+            timestamp: history::now_timestamp(),
+            wpm: wpm as i32,
+            acc: acc as i32,
+            success,
+            millis: elapsed.as_millis() as u64,
+            raw_wpm,
+            retry: self.current_lesson_retries,
+            lesson: self.current_lesson_number,
+            ngrams: self.current_lesson_ngrams.iter().flatten().cloned().collect(),
+            ngram_results,
+            transitions,
+            key_results,
+            options: Options { seed: Some(self.seed), ..self.options.clone() },
+            tier: history::Tier::of(self),
+        });
+        // a failed save must not end the session, so saving stops and the error is kept for later
+        if let Some(path) = &self.history_path {
+            if let Err(err) = history::append(path, self.history.lessons.last().unwrap()) {
+                self.save_error = Some(format!("Could not save the history to {}: {}", path.display(), err));
+                self.history_path = None;
+            }
+        }
+
+        // timed tests are benchmarks, not drills, so they neither progress nor repeat
+        if self.options.time > 0 {
+            return false;
+        }
+        progress::record_lesson(self, success);
+
+        // like ngram-type, a failed lesson can be repeated until it is passed (or out of retries)
+        !success && self.options.on_fail == "repeat"
+            && (self.options.max_retries == 0 || self.current_lesson_retries < self.options.max_retries)
+    }
+
+    // Generates the next lesson of ngrams. In a timed test, this is only the beginning
+    // of the stream.
+    fn next_lesson(&mut self) {
+        if let Some(lesson) = self.planned_lessons.pop_front() {
+            // the ngrams of a planned lesson are unknown, so it adds nothing to the ngram stats
+            self.current_lesson_string = lesson;
+            self.current_lesson_ngrams = vec![];
+        } else if self.ngrams.is_empty() {
+            // out of planned lessons without an ngram list to generate more, see with_lessons
+            self.current_lesson_ngrams = vec![];
+        } else if self.options.time > 0 {
+            (self.current_lesson_string, self.current_lesson_ngrams) = self.sample_ngrams(STREAM_CHUNK);
+        } else {
+            let (chain, lesson_ngrams) = self.sample_ngrams(self.combi);
+            // 4. repeat the chain rep times
+            self.current_lesson_string = chain.repeat(self.options.rep as usize);
+            self.current_lesson_ngrams = lesson_ngrams;
+        }
+        self.position_misses = vec![0; self.current_lesson_string.chars().count()];
+        self.recording.lessons.push(self.current_lesson_string.clone());
+    }
+
+    // Makes sure the stream of a timed test never runs out while typing.
+    fn extend_stream(&mut self) {
+        let ahead = self.current_lesson_string.chars().count() - self.current_typed_string.chars().count();
+        if ahead >= STREAM_AHEAD || self.ngrams.is_empty() {
+            return;
+        }
+        let (chunk, chunk_ngrams) = self.sample_ngrams(STREAM_CHUNK);
+        self.position_misses.extend(vec![0; chunk.chars().count()]);
+        self.current_lesson_string.push_str(&chunk);
+        self.current_lesson_ngrams.extend(chunk_ngrams);
+        if let Some(recorded) = self.recording.lessons.last_mut() {
+            recorded.clone_from(&self.current_lesson_string);
+        }
+    }
+
+    // Chooses count many ngrams from the top ngrams of the current tier, based on the
+    // options, and chains them with spaces.
+    // Also returns the ngram of every space separated token of the chain, None for injected numbers.
+    fn sample_ngrams(&mut self, count: i32) -> (String, Vec<Option<String>>) {
+
+        let mut lesson_string = String::new();
+        let mut lesson_ngrams = Vec::new();
+        let rng = &mut self.rng;
+        let mut ngrams = self.ngrams.clone();
+
+        // 1. extract top many ngrams from the ngrams
+        ngrams.truncate(self.top as usize);
+
+        // 2. randomly choose count many ngrams from the top ngrams then chain ngrams A B C ...
+        // with freq sampling, common ngrams are chosen more often,
+        // with weak sampling, ngrams we type badly are more likely to be chosen
+        let weighted = match self.options.sample.as_str() {
+            "freq" => WeightedIndex::new(&self.ngram_weights[..ngrams.len()]).ok(),
+            "weak" => WeightedIndex::new(stats::weakness_weights(&ngrams, &self.ngram_stats, self.options.explore as f64 / 100.0)).ok(),
+            _ => None,
+        };
+        for _ in 0..count {
+            let ngram = match &weighted {
+                Some(dist) => &ngrams[dist.sample(rng)],
+                None => ngrams.choose(rng).unwrap(),
+            };
+            lesson_string.push_str(&chars::decorate(ngram, &self.options.inject, rng));
+            lesson_string.push(' ');
+            lesson_ngrams.push(Some(ngram.clone()));
+            // NOTE: this also causes the string to end with a space,
+            // which is actually pretty nice
+
+            if let Some(number) = chars::number_token(&self.options.inject, rng) {
+                lesson_string.push_str(&number);
+                lesson_string.push(' ');
+                lesson_ngrams.push(None);
+            }
+        }
+
+        (lesson_string, lesson_ngrams)
+    }
+}
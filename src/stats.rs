@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::history::History;
+
+// How a single ngram performed in a single lesson.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NgramResult {
+    pub ngram: String,
+    pub chars: u32,       // correctly typed characters belonging to the ngram
+    pub misses: u32,      // wrong keystrokes while the ngram was expected
+    pub millis: u64,      // time spent on the transitions into the ngram's characters
+    pub transitions: u32, // number of transitions included in millis
+}
+
+// How long the transitions between two characters took in a single lesson.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransitionResult {
+    pub pair: String, // the two characters, e.g. "th" for the transition from t to h
+    pub millis: u64,
+    pub count: u32,
+}
+
+// How a single key performed in a single lesson, by the character it types.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct KeyResult {
+    pub key: char,
+    pub presses: u32,     // how often the key was expected
+    pub misses: u32,      // wrong keystrokes while the key was expected
+    pub wrong: u32,       // how often the key was typed while another one was expected
+    pub millis: u64,      // time spent on the transitions into the key
+    pub transitions: u32, // number of transitions included in millis
+}
+
+// Accumulated performance of a single key across lessons.
+#[derive(Default, Clone)]
+pub struct KeyStats {
+    pub presses: u32,
+    pub misses: u32,
+    pub wrong: u32,
+    pub millis: u64,
+    pub transitions: u32,
+}
+
+impl KeyStats {
+    pub fn add(&mut self, result: &KeyResult) {
+        self.presses += result.presses;
+        self.misses += result.misses;
+        self.wrong += result.wrong;
+        self.millis += result.millis;
+        self.transitions += result.transitions;
+    }
+
+    // Share of keystrokes that went wrong when this key was expected, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        let total = self.presses + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.misses as f64 / total as f64
+    }
+
+    // Average time to reach this key, or None if no transition was timed yet.
+    pub fn ms_per_press(&self) -> Option<f64> {
+        if self.transitions == 0 {
+            return None;
+        }
+        Some(self.millis as f64 / self.transitions as f64)
+    }
+}
+
+// Accumulated performance of a single ngram across lessons.
+#[derive(Default, Clone)]
+pub struct NgramStats {
+    pub chars: u32,
+    pub misses: u32,
+    pub millis: u64,
+    pub transitions: u32,
+}
+
+impl NgramStats {
+    pub fn add(&mut self, result: &NgramResult) {
+        self.chars += result.chars;
+        self.misses += result.misses;
+        self.millis += result.millis;
+        self.transitions += result.transitions;
+    }
+
+    // Share of keystrokes that were wrong, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        let total = self.chars + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.misses as f64 / total as f64
+    }
+
+    // Average time per keystroke, or None if no transition was timed yet.
+    pub fn ms_per_char(&self) -> Option<f64> {
+        if self.transitions == 0 {
+            return None;
+        }
+        Some(self.millis as f64 / self.transitions as f64)
+    }
+}
+
+// Splits a finished lesson into its space separated ngrams and measures each of them.
+// `ngrams` are the ngrams of the tokens of a single chain, in the order they appear in the
+// lesson. Tokens without an ngram (injected numbers) are skipped, characters injected around
+// an ngram count towards it.
+// `key_times` and `position_misses` are indexed by the character position in the lesson.
+pub fn measure_lesson(
+    lesson: &str,
+    ngrams: &[Option<String>],
+    key_times: &[std::time::Instant],
+    position_misses: &[u32],
+) -> Vec<NgramResult> {
+    let mut results: Vec<NgramResult> = Vec::new();
+    if ngrams.is_empty() {
+        return results;
+    }
+
+    let mut token_index = 0;
+    for (pos, c) in lesson.chars().enumerate() {
+        if c == ' ' {
+            token_index += 1;
+            continue;
+        }
+        let Some(ngram) = &ngrams[token_index % ngrams.len()] else {
+            continue;
+        };
+        let result = match results.iter_mut().position(|r| &r.ngram == ngram) {
+            Some(i) => &mut results[i],
+            None => {
+                results.push(NgramResult {
+                    ngram: ngram.clone(),
+                    chars: 0,
+                    misses: 0,
+                    millis: 0,
+                    transitions: 0,
+                });
+                results.last_mut().unwrap()
+            }
+        };
+        result.chars += 1;
+        result.misses += position_misses.get(pos).copied().unwrap_or(0);
+        // the very first key of a lesson starts the clock, so it has no transition time
+        if pos > 0 && pos < key_times.len() {
+            result.millis += key_times[pos].duration_since(key_times[pos - 1]).as_millis() as u64;
+            result.transitions += 1;
+        }
+    }
+    results
+}
+
+// Measures the latency of every transition between two non-space characters of a finished
+// lesson. Transitions into a character that was mistyped first are left out, since their
+// time includes the correction.
+pub fn measure_transitions(
+    lesson: &str,
+    key_times: &[std::time::Instant],
+    position_misses: &[u32],
+) -> Vec<TransitionResult> {
+    let mut results: Vec<TransitionResult> = Vec::new();
+    let chars: Vec<char> = lesson.chars().collect();
+    for pos in 1..chars.len().min(key_times.len()) {
+        let (from, to) = (chars[pos - 1], chars[pos]);
+        if from == ' ' || to == ' ' || position_misses.get(pos).copied().unwrap_or(0) > 0 {
+            continue;
+        }
+        let pair: String = [from, to].iter().collect();
+        let millis = key_times[pos].duration_since(key_times[pos - 1]).as_millis() as u64;
+        match results.iter_mut().find(|r| r.pair == pair) {
+            Some(result) => {
+                result.millis += millis;
+                result.count += 1;
+            }
+            None => results.push(TransitionResult { pair, millis, count: 1 }),
+        }
+    }
+    results
+}
+
+// Measures every key of a finished lesson. `wrong_keys` counts the chars that were typed
+// instead of the expected ones. Like for transitions, only keys that were typed right at
+// the first attempt are timed.
+pub fn measure_keys(
+    lesson: &str,
+    key_times: &[std::time::Instant],
+    position_misses: &[u32],
+    wrong_keys: &HashMap<char, u32>,
+) -> Vec<KeyResult> {
+    fn result_of(key: char, results: &mut Vec<KeyResult>) -> usize {
+        match results.iter().position(|r| r.key == key) {
+            Some(i) => i,
+            None => {
+                results.push(KeyResult { key, presses: 0, misses: 0, wrong: 0, millis: 0, transitions: 0 });
+                results.len() - 1
+            }
+        }
+    }
+
+    let mut results: Vec<KeyResult> = Vec::new();
+    for (pos, c) in lesson.chars().enumerate() {
+        let i = result_of(c, &mut results);
+        let misses = position_misses.get(pos).copied().unwrap_or(0);
+        results[i].presses += 1;
+        results[i].misses += misses;
+        if pos > 0 && pos < key_times.len() && misses == 0 {
+            results[i].millis += key_times[pos].duration_since(key_times[pos - 1]).as_millis() as u64;
+            results[i].transitions += 1;
+        }
+    }
+    for (key, wrong) in wrong_keys {
+        let i = result_of(*key, &mut results);
+        results[i].wrong += wrong;
+    }
+    results
+}
+
+// Accumulates the key results of all lessons in the history that were practiced on
+// the given emulated layout.
+pub fn keys_from_history(history: &History, emu_out: &str) -> HashMap<char, KeyStats> {
+    let mut stats: HashMap<char, KeyStats> = HashMap::new();
+    for lesson in history.lessons.iter().filter(|l| l.options.emu_out == emu_out) {
+        for result in &lesson.key_results {
+            stats.entry(result.key).or_default().add(result);
+        }
+    }
+    stats
+}
+
+// Returns the average latency of every transition in the history that was practiced on
+// the given emulated layout, with the number of samples, slowest first.
+pub fn slowest_transitions(history: &History, emu_out: &str) -> Vec<(String, f64, u32)> {
+    let mut totals: HashMap<String, (u64, u32)> = HashMap::new();
+    for lesson in history.lessons.iter().filter(|l| l.options.emu_out == emu_out) {
+        for result in &lesson.transitions {
+            let total = totals.entry(result.pair.clone()).or_default();
+            total.0 += result.millis;
+            total.1 += result.count;
+        }
+    }
+    let mut averages: Vec<(String, f64, u32)> = totals
+        .into_iter()
+        .map(|(pair, (millis, count))| (pair, millis as f64 / count as f64, count))
+        .collect();
+    averages.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    averages
+}
+
+// Accumulates the ngram results of all lessons in the history that were practiced on
+// the given emulated layout, since the weaknesses of one layout say nothing about another.
+pub fn from_history(history: &History, emu_out: &str) -> HashMap<String, NgramStats> {
+    let mut stats: HashMap<String, NgramStats> = HashMap::new();
+    for lesson in history.lessons.iter().filter(|l| l.options.emu_out == emu_out) {
+        for result in &lesson.ngram_results {
+            stats.entry(result.ngram.clone()).or_default().add(result);
+        }
+    }
+    stats
+}
+
+// Returns a sampling weight for every ngram, higher meaning weaker.
+// Weakness is the mean of the error rate and the time per keystroke, each relative to the
+// worst of the given ngrams. Ngrams without any data count as maximally weak so they get
+// practiced at least once. `explore` (0-1) is the weight floor every ngram gets regardless
+// of its weakness.
+pub fn weakness_weights(ngrams: &[String], stats: &HashMap<String, NgramStats>, explore: f64) -> Vec<f64> {
+    let max_err = ngrams
+        .iter()
+        .filter_map(|n| stats.get(n))
+        .map(|s| s.error_rate())
+        .fold(0.0, f64::max);
+    let max_ms = ngrams
+        .iter()
+        .filter_map(|n| stats.get(n))
+        .filter_map(|s| s.ms_per_char())
+        .fold(0.0, f64::max);
+
+    ngrams
+        .iter()
+        .map(|n| {
+            let weakness = match stats.get(n) {
+                Some(s) if s.chars > 0 => {
+                    let err = if max_err > 0.0 { s.error_rate() / max_err } else { 0.0 };
+                    let ms = match s.ms_per_char() {
+                        Some(ms) if max_ms > 0.0 => ms / max_ms,
+                        _ => 1.0,
+                    };
+                    (err + ms) / 2.0
+                }
+                _ => 1.0,
+            };
+            explore + (1.0 - explore) * weakness
+        })
+        .collect()
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::session::Session;
+
+// How many of the most missed ngrams and keys are listed.
+const MAX_MISSED: usize = 5;
+
+// The results of a practice session, shown when the program is quit.
+pub struct Summary {
+    pub lessons: usize,
+    pub passed: i32,
+    pub avg_wpm: i32,
+    pub best_wpm: i32,
+    pub worst_wpm: i32,
+    pub avg_acc: i32,
+    pub best_acc: i32,
+    pub worst_acc: i32,
+    pub missed_ngrams: Vec<(String, u32)>,
+    pub missed_keys: Vec<(char, u32)>,
+    pub practiced: std::time::Duration, // time spent typing, not counting breaks between lessons
+    pub seed: u64,
+}
+
+impl Summary {
+    // Summarizes the lessons finished since the program was started,
+    // or returns None if there are none.
+    pub fn from_session(session: &Session) -> Option<Self> {
+        let lessons = &session.history.lessons[session.session_start..];
+        if lessons.is_empty() {
+            return None;
+        }
+
+        let mut ngram_misses: HashMap<&str, u32> = HashMap::new();
+        for result in lessons.iter().flat_map(|l| &l.ngram_results) {
+            *ngram_misses.entry(&result.ngram).or_default() += result.misses;
+        }
+        let missed_ngrams = most_missed(ngram_misses.into_iter().map(|(n, m)| (n.to_string(), m)));
+        let mut key_misses: HashMap<char, u32> = HashMap::new();
+        for result in lessons.iter().flat_map(|l| &l.key_results) {
+            *key_misses.entry(result.key).or_default() += result.misses;
+        }
+        let missed_keys = most_missed(key_misses.into_iter());
+
+        Some(Summary {
+            lessons: lessons.len(),
+            passed: session.succeeded_lessons,
+            avg_wpm: session.average_wpm,
+            best_wpm: session.wpm_history.iter().copied().max().unwrap_or(0),
+            worst_wpm: session.wpm_history.iter().copied().min().unwrap_or(0),
+            avg_acc: session.average_accuracy,
+            best_acc: session.acc_history.iter().copied().max().unwrap_or(0),
+            worst_acc: session.acc_history.iter().copied().min().unwrap_or(0),
+            missed_ngrams,
+            missed_keys,
+            practiced: std::time::Duration::from_millis(lessons.iter().map(|l| l.millis).sum()),
+            seed: session.seed,
+        })
+    }
+
+    // The summary as plain text lines, used both on screen and on stdout.
+    pub fn lines(&self) -> Vec<String> {
+        let secs = self.practiced.as_secs();
+        let mut lines = vec![
+            format!(
+                "Lessons:       {} ({} passed, {}%)",
+                self.lessons,
+                self.passed,
+                self.passed as usize * 100 / self.lessons
+            ),
+            format!(
+                "WPM:           avg. {}, best {}, worst {}",
+                self.avg_wpm, self.best_wpm, self.worst_wpm
+            ),
+            format!(
+                "Accuracy:      avg. {}%, best {}%, worst {}%",
+                self.avg_acc, self.best_acc, self.worst_acc
+            ),
+            format!("Practiced:     {}m {:02}s", secs / 60, secs % 60),
+            format!("Seed:          {}", self.seed),
+        ];
+        if !self.missed_ngrams.is_empty() {
+            let ngrams: Vec<String> = self.missed_ngrams.iter().map(|(n, m)| format!("{} ({})", n, m)).collect();
+            lines.push(format!("Missed ngrams: {}", ngrams.join(", ")));
+        }
+        if !self.missed_keys.is_empty() {
+            let keys: Vec<String> = self.missed_keys.iter().map(|(k, m)| format!("{} ({})", key_name(*k), m)).collect();
+            lines.push(format!("Missed keys:   {}", keys.join(", ")));
+        }
+        lines
+    }
+}
+
+// Returns the entries with the most misses, most first. Ties are ordered by their key
+// so the summary does not change between runs.
+fn most_missed<T: Ord>(misses: impl Iterator<Item = (T, u32)>) -> Vec<(T, u32)> {
+    let mut missed: Vec<(T, u32)> = misses.filter(|(_, m)| *m > 0).collect();
+    missed.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    missed.truncate(MAX_MISSED);
+    missed
+}
+
+fn key_name(c: char) -> String {
+    match c {
+        ' ' => "space".to_string(),
+        c => c.to_string(),
+    }
+}
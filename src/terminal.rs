@@ -0,0 +1,261 @@
+use std::io::{stdout, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use crossterm::event::{self, KeyEvent, KeyEventKind, KeyModifiers, PopKeyboardEnhancementFlags};
+use crossterm::ExecutableCommand;
+
+use ngrrram::input::{InputSource, KeyCode, KeyInput};
+
+// Reads key presses from the terminal. When emulating a layout and the terminal supports the
+// kitty keyboard protocol, the protocol is enabled and the escape codes are read and parsed
+// here, because crossterm drops the base layout key and the text of the protocol. Otherwise
+// crossterm events are used as usual.
+pub struct TerminalInput {
+    kitty: Option<Receiver<u8>>,
+    pending: Vec<u8>,
+}
+
+impl TerminalInput {
+    // Must be called after raw mode is enabled. try_kitty should only be set when emulating,
+    // since the protocol is only needed for the base layout keys.
+    pub fn new(try_kitty: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut input = TerminalInput {
+            kitty: None,
+            pending: Vec::new(),
+        };
+        if try_kitty && crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false) {
+            // disambiguate escape codes (1), report alternate keys (4), report all keys as
+            // escape codes (8) and report associated text (16). crossterm has no flag for
+            // the text, so the flags are pushed by hand.
+            let mut out = stdout();
+            out.write_all(b"\x1b[>29u")?;
+            out.flush()?;
+            // reading stdin blocks, so it gets its own thread that lives as long as the program
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                for byte in std::io::stdin().lock().bytes() {
+                    match byte {
+                        Ok(byte) if sender.send(byte).is_ok() => {}
+                        _ => break,
+                    }
+                }
+            });
+            input.kitty = Some(receiver);
+        }
+        Ok(input)
+    }
+}
+
+impl InputSource for TerminalInput {
+    fn poll_key(&mut self, timeout: Duration) -> Result<Option<KeyInput>, Box<dyn std::error::Error>> {
+        let Some(receiver) = &self.kitty else {
+            if event::poll(timeout)? {
+                if let event::Event::Key(key) = event::read()? {
+                    return Ok(from_crossterm(key));
+                }
+            }
+            return Ok(None);
+        };
+
+        // collect everything that arrived, waiting only for the first byte
+        match receiver.recv_timeout(timeout) {
+            Ok(byte) => self.pending.push(byte),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Err("stdin was closed".into()),
+        }
+        self.pending.extend(receiver.try_iter());
+
+        while !self.pending.is_empty() {
+            let (consumed, key) = parse_kitty(&self.pending);
+            if consumed == 0 {
+                break; // incomplete sequence, wait for the rest
+            }
+            self.pending.drain(..consumed);
+            if key.is_some() {
+                return Ok(key);
+            }
+        }
+        Ok(None)
+    }
+
+    fn reports_base_keys(&self) -> bool {
+        self.kitty.is_some()
+    }
+
+    // Restores the keyboard mode the terminal had before.
+    fn cleanup(&self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.kitty.is_some() {
+            stdout().execute(PopKeyboardEnhancementFlags)?;
+        }
+        Ok(())
+    }
+}
+
+// Converts a crossterm key press, None for releases and keys the session does not use.
+fn from_crossterm(key: KeyEvent) -> Option<KeyInput> {
+    if key.kind != KeyEventKind::Press {
+        return None;
+    }
+    let code = match key.code {
+        event::KeyCode::Char(c) => KeyCode::Char(c),
+        event::KeyCode::Enter => KeyCode::Enter,
+        event::KeyCode::Tab => KeyCode::Tab,
+        event::KeyCode::Backspace => KeyCode::Backspace,
+        event::KeyCode::Esc => KeyCode::Esc,
+        _ => return None,
+    };
+    Some(KeyInput {
+        ctrl: key.modifiers.contains(KeyModifiers::CONTROL),
+        alt: key.modifiers.contains(KeyModifiers::ALT),
+        shifted: key.modifiers.contains(KeyModifiers::SHIFT),
+        ..KeyInput::new(code)
+    })
+}
+
+// Parses the first key press in bytes. Returns how many bytes were consumed (0 if the
+// sequence is not complete yet) and the key, if the bytes were a key press we care about.
+//
+// With the enabled flags, every key is sent as
+//     CSI unicode-key-code:shifted-key:base-layout-key ; modifiers ; text u
+// where the shifted and base layout keys and the text are optional.
+// See <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+fn parse_kitty(bytes: &[u8]) -> (usize, Option<KeyInput>) {
+    if !bytes.starts_with(b"\x1b[") {
+        if bytes[0] == 0x1b {
+            if bytes.len() == 1 {
+                return (0, None);
+            }
+            return (1, None); // some escape sequence we do not know, skip it byte by byte
+        }
+        // plain text should not arrive with the protocol enabled, but take it anyway
+        let len = utf8_len(bytes[0]);
+        if bytes.len() < len {
+            return (0, None);
+        }
+        let key = std::str::from_utf8(&bytes[..len])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .map(|c| KeyInput::new(KeyCode::Char(c)));
+        return (len, key);
+    }
+
+    // a CSI sequence ends with a byte in the range 0x40-0x7e
+    let Some(end) = bytes.iter().skip(2).position(|b| (0x40..=0x7e).contains(b)).map(|p| p + 2) else {
+        return (0, None);
+    };
+    let consumed = end + 1;
+    if bytes[end] != b'u' {
+        return (consumed, None); // arrows, function keys and so on
+    }
+    let Ok(params) = std::str::from_utf8(&bytes[2..end]) else {
+        return (consumed, None);
+    };
+
+    let mut fields = params.split(';');
+    let mut codes = fields.next().unwrap_or("").split(':');
+    let code = codes.next().and_then(|c| c.parse::<u32>().ok()).and_then(char::from_u32);
+    let shifted_key = codes.next().and_then(|c| c.parse::<u32>().ok()).and_then(char::from_u32);
+    let base = codes.next().and_then(|c| c.parse::<u32>().ok()).and_then(char::from_u32);
+    let mut modifiers_and_kind = fields.next().unwrap_or("1").split(':');
+    // the text is a list of code points, of which only a single char is of use to us
+    let text = fields.next().and_then(|t| t.split(':').next()).and_then(|c| c.parse::<u32>().ok()).and_then(char::from_u32);
+    // 1 + the modifier bits, which is 256 with all eight of them
+    let modifier_mask = modifiers_and_kind.next().and_then(|m| m.parse::<u32>().ok()).unwrap_or(1).saturating_sub(1);
+    // 1 is a press, 2 a repeat and 3 a release. Only presses are reported with our flags.
+    if matches!(modifiers_and_kind.next(), Some("2") | Some("3")) {
+        return (consumed, None);
+    }
+
+    let Some(code) = code else {
+        return (consumed, None);
+    };
+    let shifted = modifier_mask & 0b1 != 0;
+    let alt = modifier_mask & 0b10 != 0;
+    let ctrl = modifier_mask & 0b100 != 0;
+    // the remaining bits are super, hyper, meta, caps lock and num lock, of which only caps lock matters
+    let caps_lock = modifier_mask & 0b100_0000 != 0;
+
+    // AltGr, dead keys and compose produce text that is not on the key itself, so its
+    // physical position says nothing about the char. Caps lock only changes the case.
+    let from_key = |t: char| t == code || Some(t) == shifted_key || t.to_lowercase().eq(code.to_lowercase());
+    let composed = text.filter(|t| !from_key(*t));
+
+    let key_code = match code {
+        '\x1b' => KeyCode::Esc,
+        '\r' => KeyCode::Enter,
+        '\t' => KeyCode::Tab,
+        '\x7f' => KeyCode::Backspace,
+        // modifier keys and other functional keys live in the unicode private use area
+        '\u{e000}'..='\u{f8ff}' => return (consumed, None),
+        c => match (text, shifted_key) {
+            // the text is what the system layout typed, with shift, caps lock and AltGr
+            (Some(text), _) => KeyCode::Char(text),
+            // like crossterm, report the shifted character
+            (None, Some(shifted_key)) if shifted => KeyCode::Char(shifted_key),
+            _ => KeyCode::Char(c),
+        },
+    };
+
+    // the base layout key is only sent if it differs from the unicode key code
+    let base = match key_code {
+        KeyCode::Char(_) if composed.is_none() => Some(base.unwrap_or(code)),
+        _ => None,
+    };
+    (consumed, Some(KeyInput { code: key_code, ctrl, alt, base, shifted, caps_lock }))
+}
+
+fn utf8_len(first_byte: u8) -> usize {
+    match first_byte {
+        0xf0..=0xff => 4,
+        0xe0..=0xef => 3,
+        0xc0..=0xdf => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, base: Option<char>) -> Option<KeyInput> {
+        Some(KeyInput { base, ..KeyInput::new(code) })
+    }
+
+    #[test]
+    fn parse_kitty_sequences() {
+        let a = press(KeyCode::Char('a'), Some('a'));
+        let cases: Vec<(&str, &[u8], usize, Option<KeyInput>)> = vec![
+            ("incomplete escape", b"\x1b", 0, None),
+            ("incomplete csi", b"\x1b[97;", 0, None),
+            ("incomplete utf-8", b"\xc3", 0, None),
+            ("unknown escape", b"\x1bOP", 1, None),
+            ("arrow", b"\x1b[A", 3, None),
+            ("plain text", "ü".as_bytes(), 2, press(KeyCode::Char('ü'), None)),
+            ("press", b"\x1b[97u", 5, a),
+            ("only the first key", b"\x1b[97u\x1b[98u", 5, a),
+            ("repeat", b"\x1b[97;1:2u", 9, None),
+            ("release", b"\x1b[97;1:3u", 9, None),
+            ("esc", b"\x1b[27u", 5, press(KeyCode::Esc, None)),
+            ("enter", b"\x1b[13u", 5, press(KeyCode::Enter, None)),
+            ("backspace", b"\x1b[127u", 6, press(KeyCode::Backspace, None)),
+            ("ctrl", b"\x1b[99;5u", 7, press(KeyCode::Char('c'), Some('c')).map(|k| KeyInput { ctrl: true, ..k })),
+            ("shift", b"\x1b[97:65;2;65u", 13, press(KeyCode::Char('A'), Some('a')).map(|k| KeyInput { shifted: true, ..k })),
+            ("shift without text", b"\x1b[97:65;2u", 10, press(KeyCode::Char('A'), Some('a')).map(|k| KeyInput { shifted: true, ..k })),
+            ("caps lock", b"\x1b[97;65;65u", 11, press(KeyCode::Char('A'), Some('a')).map(|k| KeyInput { caps_lock: true, ..k })),
+            ("base key of another layout", b"\x1b[246::59;1;246u", 16, press(KeyCode::Char('ö'), Some(';'))),
+            ("altgr text", b"\x1b[55;1;123u", 11, press(KeyCode::Char('{'), None)),
+            ("composed text", b"\x1b[101;1;233u", 12, press(KeyCode::Char('é'), None)),
+            ("private use key", b"\x1b[57441;2u", 10, None),
+            (
+                "all modifiers",
+                b"\x1b[97;256u",
+                9,
+                press(KeyCode::Char('a'), Some('a')).map(|k| KeyInput { ctrl: true, alt: true, shifted: true, caps_lock: true, ..k }),
+            ),
+        ];
+        for (name, bytes, consumed, key) in cases {
+            assert_eq!(parse_kitty(bytes), (consumed, key), "{}", name);
+        }
+    }
+}
@@ -7,30 +7,381 @@ use crossterm::{
 };
 
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect}, prelude::{CrosstermBackend, Stylize, Terminal}, symbols::border, terminal::Frame, text::{Line, Span}, widgets::{block::Title, Block, Borders, Paragraph}
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect}, prelude::{CrosstermBackend, Stylize, Terminal}, style::{Color, Style}, symbols::{self, border}, terminal::Frame, text::{Line, Span}, widgets::{block::Title, Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph}
 };
 
+use std::collections::HashMap;
 use std::io::{self, stdout, Stdout};
 
-use crate::AppState;
-use crate::Args;
+use ngrrram::layout::{self, Finger};
+use ngrrram::race::Ghost;
+use ngrrram::stats::KeyStats;
+use ngrrram::summary::Summary;
+use ngrrram::Snapshot;
 
-fn render(frame: &mut Frame, state: &AppState, args: &Args, kb_string: &String, cat_string: &String) {
-    let area = match args.nokb {
-        true => Rect { x: 0, y: 0, width: 79, height: 13 },
-        false => Rect { x: 0, y: 0, width: 79, height: 19 },
+// The rows the wpm and accuracy graphs add below the stats with --graphs.
+const GRAPHS_HEIGHT: u16 = 9;
+// The ui never gets wider than this, larger terminals get it centered.
+const MAX_WIDTH: u16 = 79;
+// Below this size not even the lesson and the stats fit, so we ask for a larger terminal.
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 10;
+// Long lessons are wrapped, and at most this many lines are shown at once.
+const MAX_LESSON_LINES: u16 = 3;
+// How long the key of a wrong char flashes red.
+const MISS_FLASH_MILLIS: u128 = 300;
+// The cat sits on the stats to the right of the keyboard, so it needs the full width.
+const CAT_WIDTH: u16 = 15;
+
+// What to show besides the lesson and the stats. Practice and replays share these flags.
+#[derive(clap::Args)]
+pub struct DisplayArgs {
+    #[arg(
+        long,
+        action,
+        default_value = "false",
+        value_name = "bool",
+        help = "show keyboard in ortholinear format"
+    )]
+    pub show_ortho: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "pass this flag to color the keyboard by finger and show which finger types the next character."
+    )]
+    pub fingers: bool,
+
+    #[arg(
+        long,
+        default_value = "",
+        value_name = "errors|latency",
+        help = "tint the keys of the keyboard by the error rate or average latency on them, based on the history or the replayed lessons."
+    )]
+    pub heatmap: String,
+
+    #[arg(
+        long,
+        action,
+        help = "pass this flag to disable the keyboard layout display."
+    )]
+    pub nokb: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "pass this flag to show graphs of the wpm and accuracy over the session."
+    )]
+    pub graphs: bool,
+
+    #[arg(long, action, help = "the most important flag. don't practice alone.")]
+    pub cat: bool,
+}
+
+impl DisplayArgs {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.heatmap.is_empty() && self.heatmap != "errors" && self.heatmap != "latency" {
+            return Err("Invalid argument for heatmap. Use errors or latency.".to_string());
+        }
+        Ok(())
+    }
+}
+
+// The keyboard of the emulated layout, rendered in both sizes.
+pub struct Keyboards {
+    pub full: layout::Keyboard,
+    pub compact: layout::Keyboard,
+}
+
+// Where everything goes on screen. Panels that do not fit are None.
+struct Panels<'a> {
+    outline: Rect,
+    header: Option<Rect>,
+    info: Rect,
+    lesson: Rect,
+    lesson_lines: u16, // how many lines of the lesson are shown
+    lesson_width: u16, // the width the lesson is wrapped at
+    stats: Rect,
+    graphs: Option<Rect>,
+    keyboard: Option<&'a layout::Keyboard>,
+    full_keyboard: bool,
+}
+
+// The rows of the lesson panel above the keyboard: a blank line, then every lesson line
+// followed by the line of corrections.
+fn lesson_height(lesson_lines: u16) -> u16 {
+    1 + 2 * lesson_lines
+}
+
+// Splits the lesson into lines of at most width characters, breaking after spaces so
+// words stay together. Words longer than a line are split anyway.
+// Returns the range of character indices of every line.
+fn wrap_lesson(lesson: &[char], width: usize) -> Vec<std::ops::Range<usize>> {
+    let mut lines: Vec<std::ops::Range<usize>> = Vec::new();
+    let mut line_start = 0;
+    let mut word_start = 0;
+    for (idx, c) in lesson.iter().enumerate() {
+        if *c != ' ' && idx + 1 != lesson.len() {
+            continue;
+        }
+        // the word, including its trailing space, goes from word_start to idx
+        let word_end = idx + 1;
+        if word_end - line_start > width && word_start > line_start {
+            lines.push(line_start..word_start);
+            line_start = word_start;
+        }
+        while word_end - line_start > width {
+            lines.push(line_start..line_start + width);
+            line_start += width;
+        }
+        word_start = word_end;
+    }
+    if line_start < lesson.len() || lines.is_empty() {
+        lines.push(line_start..lesson.len());
+    }
+    lines
+}
+
+// Where the cursor goes after cursor chars of the wrapped lesson: the index of its line,
+// the first of the shown lines and its column in a panel of the given width. We scroll so
+// the line with the cursor is always the first one shown, unless we are at the last lines
+// of the lesson. Every line is centered on its own, the same way the paragraph does it.
+fn cursor_position(lines: &[std::ops::Range<usize>], cursor: usize, shown: usize, width: usize) -> (usize, usize, usize) {
+    let cursor_line = lines.iter().position(|line| cursor < line.end).unwrap_or(lines.len() - 1);
+    let first_line = cursor_line.min(lines.len().saturating_sub(shown));
+    let line = &lines[cursor_line];
+    let line_offset = width.saturating_sub(line.len()) / 2;
+    (cursor_line, first_line, line_offset + cursor.min(line.end) - line.start)
+}
+
+// Both hands use the same colors, so a color always means the same finger.
+fn finger_color(finger: Finger) -> Color {
+    match finger {
+        Finger::LeftPinky | Finger::RightPinky => Color::Magenta,
+        Finger::LeftRing | Finger::RightRing => Color::Blue,
+        Finger::LeftMiddle | Finger::RightMiddle => Color::Green,
+        Finger::LeftIndex | Finger::RightIndex => Color::Yellow,
+        Finger::Thumb => Color::Gray,
+    }
+}
+
+// Returns the tint of every key for the --heatmap kind.
+// Every char of a key (with and without shift) counts towards it. Keys are tinted by where
+// they are between the best and the worst key, keys in the better quarter are left alone.
+fn heatmap_tints<'a>(keyboard: &'a layout::Keyboard, key_stats: &HashMap<char, KeyStats>, kind: &str) -> Vec<(&'a layout::KeyCell, Color)> {
+    let mut by_cell: HashMap<(usize, usize), (&layout::KeyCell, KeyStats)> = HashMap::new();
+    for (c, stats) in key_stats {
+        if let Some(cell) = keyboard.cell_of(*c) {
+            let (_, key) = by_cell.entry((cell.line, cell.column)).or_insert((cell, KeyStats::default()));
+            key.presses += stats.presses;
+            key.misses += stats.misses;
+            key.millis += stats.millis;
+            key.transitions += stats.transitions;
+        }
+    }
+    let values: Vec<(&layout::KeyCell, f64)> = by_cell
+        .into_values()
+        .filter_map(|(cell, stats)| match kind {
+            "errors" => Some((cell, stats.error_rate())),
+            _ => stats.ms_per_press().map(|ms| (cell, ms)),
+        })
+        .collect();
+    let min = values.iter().map(|(_, v)| *v).fold(f64::INFINITY, f64::min);
+    let max = values.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    if max <= min {
+        return Vec::new();
+    }
+    values
+        .into_iter()
+        .filter_map(|(cell, value)| {
+            let heat = (value - min) / (max - min);
+            let color = if heat >= 0.75 {
+                Color::Red
+            } else if heat >= 0.5 {
+                Color::LightRed
+            } else if heat >= 0.25 {
+                Color::Yellow
+            } else {
+                return None;
+            };
+            Some((cell, color))
+        })
+        .collect()
+}
+
+// Styles the keys that type the given chars on the keyboard, the rest of it is gray,
+// or colored by finger with --fingers, or tinted by the heatmap.
+// Later highlights win over earlier ones on the same key.
+fn keyboard_lines(keyboard: &layout::Keyboard, highlights: &[(char, Style)], fingers: bool, tints: &[(&layout::KeyCell, Color)]) -> Vec<Line<'static>> {
+    let mut styles: Vec<Vec<Style>> = keyboard
+        .lines
+        .iter()
+        .map(|line| vec![Style::default().fg(Color::Gray); line.chars().count()])
+        .collect();
+    if fingers {
+        for (cell, finger) in keyboard.keys() {
+            for style_of_char in styles[cell.line].iter_mut().skip(cell.column).take(cell.width) {
+                *style_of_char = Style::default().fg(finger_color(finger));
+            }
+        }
+    }
+    for (cell, color) in tints {
+        for style_of_char in styles[cell.line].iter_mut().skip(cell.column).take(cell.width) {
+            *style_of_char = Style::default().fg(Color::Black).bg(*color);
+        }
+    }
+    for (c, style) in highlights {
+        if let Some(cell) = keyboard.cell_of(*c) {
+            for style_of_char in styles[cell.line].iter_mut().skip(cell.column).take(cell.width) {
+                *style_of_char = *style;
+            }
+        }
+    }
+    keyboard
+        .lines
+        .iter()
+        .zip(styles)
+        .map(|(line, styles)| {
+            Line::from(
+                line.chars()
+                    .zip(styles)
+                    .map(|(c, style)| Span::styled(c.to_string(), style))
+                    .collect::<Vec<Span>>(),
+            )
+        })
+        .collect()
+}
+
+fn text_size(text: &str) -> (u16, u16) {
+    let width = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    (width as u16, text.lines().count() as u16)
+}
+
+// Lays out the ui for the given terminal size. The lesson and the stats are always shown,
+// the other panels are added by priority as long as there is room: the keyboard (the
+// compact one first), the header, the full keyboard instead of the compact one and the graphs.
+// Returns None if the terminal is too small for even the lesson and the stats.
+fn layout_panels<'a>(size: Rect, display: &DisplayArgs, keyboards: &'a Keyboards, lesson: &[char]) -> Option<Panels<'a>> {
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        return None;
+    }
+    let width = size.width.min(MAX_WIDTH);
+    let inner_width = width - 2;
+    // keep the lesson off the border, so the cursor stays visible at the end of a line
+    let lesson_width = inner_width - 4;
+    let needed_lines = (wrap_lesson(lesson, lesson_width as usize).len() as u16).min(MAX_LESSON_LINES);
+    // outline, lesson number, lesson and stats, each with a border below except the stats
+    let mut height = 2 + 2 + lesson_height(1) + 1 + 2;
+    let mut fits = |rows: u16| {
+        if height + rows > size.height {
+            return false;
+        }
+        height += rows;
+        true
+    };
+
+    let (full_width, full_height) = (keyboards.full.width(), keyboards.full.height());
+    let (compact_width, compact_height) = (keyboards.compact.width(), keyboards.compact.height());
+    // a second line to see what comes next matters more than anything else,
+    // more lines are only added once the full keyboard is shown
+    let mut lesson_lines = 1;
+    if needed_lines > 1 && fits(2) {
+        lesson_lines += 1;
+    }
+    let mut keyboard = None;
+    if !display.nokb && compact_width <= inner_width && fits(compact_height) {
+        keyboard = Some(&keyboards.compact);
+    }
+    let header = fits(2);
+    if keyboard.is_some() && full_width <= inner_width && fits(full_height - compact_height) {
+        keyboard = Some(&keyboards.full);
+    }
+    while lesson_lines < needed_lines && fits(2) {
+        lesson_lines += 1;
+    }
+    let graphs = display.graphs && fits(GRAPHS_HEIGHT);
+
+    let outline = Rect {
+        x: size.x + (size.width - width) / 2,
+        y: size.y + (size.height - height) / 2,
+        width,
+        height,
     };
-    let inner = area.inner(&Margin::new(1, 1));
+    let keyboard_height = keyboard.map(|kb| kb.height()).unwrap_or(0);
+    let mut constraints = vec![
+        Constraint::Length(if header { 2 } else { 0 }), // Title header
+        Constraint::Length(2), // lesson number + successes and fails
+        Constraint::Length(lesson_height(lesson_lines) + keyboard_height + 1), // lesson content
+        Constraint::Length(2), // wpm and accuracy stats and threshold
+    ];
+    if graphs {
+        constraints.push(Constraint::Length(GRAPHS_HEIGHT)); // wpm and accuracy graphs
+    }
     let layout = Layout::default()
         .direction(ratatui::layout::Direction::Vertical)
-        .constraints(vec![
-            Constraint::Min(2), // Title header
-            Constraint::Min(2), // lesson number + successes and fails
-            Constraint::Percentage(100), // lesson content
-            Constraint::Min(2), // wpm and accuracy stats and threshold
-        ])
-        .split(inner);
+        .constraints(constraints)
+        .split(outline.inner(&Margin::new(1, 1)));
+
+    Some(Panels {
+        outline,
+        header: header.then_some(layout[0]),
+        info: layout[1],
+        lesson: layout[2],
+        lesson_lines,
+        lesson_width,
+        stats: layout[3],
+        graphs: graphs.then(|| layout[4]),
+        keyboard,
+        full_keyboard: keyboard.is_some_and(|kb| std::ptr::eq(kb, &keyboards.full)),
+    })
+}
+
+// Draws a graph of the values of a session, one point per lesson, along with a line at the
+// threshold a lesson has to reach. The y axis always includes the threshold and is rounded
+// to tens, so the graph does not jump around with every lesson. Percentages end at 100,
+// other values start at 0.
+fn render_graph(frame: &mut Frame, area: Rect, title: &str, values: &[i32], threshold: i32, percent: bool, color: Color) {
+    let points: Vec<(f64, f64)> = values.iter().enumerate().map(|(i, v)| (i as f64, *v as f64)).collect();
+    let last_x = (values.len().max(2) - 1) as f64;
+    let threshold_points = [(0.0, threshold as f64), (last_x, threshold as f64)];
+
+    let max = values.iter().copied().max().unwrap_or(0).max(threshold);
+    let min = values.iter().copied().min().unwrap_or(threshold).min(threshold);
+    let (low, high) = if percent {
+        (min.max(0) - min.max(0) % 10, 100)
+    } else {
+        (0, max + 10 - max % 10)
+    };
+
+    let datasets = vec![
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::DarkGray))
+            .data(&threshold_points),
+        Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(color))
+            .data(&points),
+    ];
+    let chart = Chart::new(datasets)
+        .block(Block::default().title(Title::from(format!(" {} ", title).bold())))
+        .x_axis(Axis::default().bounds([0.0, last_x]))
+        .y_axis(
+            Axis::default()
+                .bounds([low as f64, high as f64])
+                .labels(vec![format!("{:3}", low).gray(), format!("{:3}", high).gray()]),
+        );
+    frame.render_widget(chart, area);
+}
 
+fn render(frame: &mut Frame, state: &Snapshot, display: &DisplayArgs, keyboards: &Keyboards, ghost: Option<&Ghost>, cat_string: &String) {
+    let lesson_chars: Vec<char> = state.lesson.chars().collect();
+    let typed_chars: Vec<char> = state.typed.chars().collect();
+    let Some(panels) = layout_panels(frame.size(), display, keyboards, &lesson_chars) else {
+        return;
+    };
 
     let help: Title = Title::from(Line::from(vec![
         " Quit ".into(),
@@ -44,120 +395,181 @@ fn render(frame: &mut Frame, state: &AppState, args: &Args, kb_string: &String,
 
     frame.render_widget(
         outline,
-        area,
+        panels.outline,
     );
 
-    let title_block: Block = Block::default()
-        .borders(Borders::BOTTOM)
-        .border_set(border::ROUNDED);
-    frame.render_widget(
-        title_block,
-        layout[0],
-    );
-    let title: Paragraph = Paragraph::new(Line::from(vec![
-        "               ngrrram!".bold(),
-        "   by winterveil".italic().gray(),
-    ]));
-    frame.render_widget(
-        title.alignment(Alignment::Center),
-        Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(vec![
-                Constraint::Percentage(100),
-            ])
-            .split(layout[0])[0],
-    );
+    if let Some(header) = panels.header {
+        let title_block: Block = Block::default()
+            .borders(Borders::BOTTOM)
+            .border_set(border::ROUNDED);
+        frame.render_widget(
+            title_block,
+            header,
+        );
+        let title: Paragraph = Paragraph::new(Line::from(vec![
+            "               ngrrram!".bold(),
+            "   by winterveil".italic().gray(),
+        ]));
+        frame.render_widget(
+            title.alignment(Alignment::Center),
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![
+                    Constraint::Percentage(100),
+                ])
+                .split(header)[0],
+        );
+        if let Some(tier) = &state.tier {
+            let tier: Paragraph = Paragraph::new(
+                format!("  {}", tier).gray()
+            ).alignment(Alignment::Left);
+            frame.render_widget(
+                tier,
+                header,
+            );
+        }
+    }
 
     let lesson_stats_block: Block = Block::default()
         .borders(Borders::BOTTOM)
         .border_set(border::ROUNDED);
     frame.render_widget(
         lesson_stats_block,
-        layout[1],
+        panels.info,
     );
-    let current_lesson_number = state.current_lesson_number;
+    let current_lesson_number = state.lesson_number;
     let lesson_number: Paragraph = Paragraph::new(
-        format!("    Lesson #{current_lesson_number}")
+        match (state.time_left, state.retries) {
+            (Some(time_left), _) => {
+                format!("    Test #{current_lesson_number}, {}s left", time_left.as_secs_f64().ceil())
+            }
+            (_, 0) => format!("    Lesson #{current_lesson_number}"),
+            (_, retries) => format!("    Lesson #{current_lesson_number}, retry {retries}"),
+        }
     ).alignment(Alignment::Left);
 
-    let successes = state.succeeded_lessons;
-    let fails = state.failed_lessons;
-    let lesson_stats: Paragraph = Paragraph::new(
-        Line::from(vec![
-            format!("✔: {}, ", successes).green(),
-            format!("✘: {}    ", fails).red(),
-        ])
-    ).alignment(Alignment::Right);
+    let successes = state.passed;
+    let fails = state.failed;
+    let mut lesson_stats_line = Line::from(vec![
+        format!("✔: {}, ", successes).green(),
+        format!("✘: {}    ", fails).red(),
+    ]);
+    // how far ahead of the ghost we were when we typed the last char
+    let ahead = ghost
+        .zip(state.typed_at)
+        .and_then(|(ghost, typed_at)| ghost.ahead_millis(state.lesson_number, typed_chars.len(), typed_at));
+    if let Some(ahead) = ahead {
+        let ahead_span = Span::from(format!("{:+} ms ahead   ", ahead));
+        lesson_stats_line.spans.insert(0, if ahead >= 0 { ahead_span.green() } else { ahead_span.red() });
+    }
+    let lesson_stats: Paragraph = Paragraph::new(lesson_stats_line).alignment(Alignment::Right);
     frame.render_widget(
         lesson_number,
-        layout[1],
+        panels.info,
     );
     frame.render_widget(
         lesson_stats,
-        layout[1],
+        panels.info,
     );
+    if display.fingers {
+        let next = lesson_chars.get(typed_chars.len()).and_then(|c| keyboards.full.finger_of(*c));
+        if let Some(finger) = next {
+            let finger_hint: Paragraph = Paragraph::new(
+                Span::from(finger.name()).fg(finger_color(finger))
+            ).alignment(Alignment::Center);
+            frame.render_widget(
+                finger_hint,
+                panels.info,
+            );
+        }
+    }
 
     let lesson_block: Block = Block::default()
         .borders(Borders::BOTTOM)
         .border_set(border::ROUNDED);
     frame.render_widget(
         lesson_block,
-        layout[2],
+        panels.lesson,
     );
 
-    // we have 9 lines of inner content here
-    let mut correction_line: String = "".to_string();
-    let mut lesson_letters: Vec<Span> = Vec::new();
-    for (idx, c) in state.current_lesson_string.chars().enumerate() {
-        let typed = state.current_typed_string.chars().nth(idx);
-        let (span, correction) = if typed == Some(c) {
-            (Span::from(c.to_string()).green().bold(), ' ')
-        } else if typed.is_some() {
-            if c == ' ' { // we display mistyped spaces as dots so they are more visible
-                (Span::from("•").red().bold(), typed.unwrap())
+    let lines = wrap_lesson(&lesson_chars, panels.lesson_width as usize);
+    let cursor = typed_chars.len();
+    let (cursor_line, first_line, cursor_x) =
+        cursor_position(&lines, cursor, panels.lesson_lines as usize, panels.lesson.width as usize);
+    let shown_lines = &lines[first_line..lines.len().min(first_line + panels.lesson_lines as usize)];
+
+    // the ghost waits at the start of the lesson until we type the first key
+    let ghost_position = ghost.and_then(|ghost| ghost.position(state.lesson_number, state.elapsed.unwrap_or_default()));
+
+    let mut lesson_text: Vec<Line> = vec!["".into()];
+    for line in shown_lines {
+        let mut correction_line: String = "".to_string();
+        let mut lesson_letters: Vec<Span> = Vec::new();
+        for idx in line.clone() {
+            let c = lesson_chars[idx];
+            let typed = typed_chars.get(idx).copied();
+            let (span, correction) = if typed == Some(c) {
+                (Span::from(c.to_string()).green().bold(), ' ')
+            } else if let Some(typed) = typed {
+                if c == ' ' { // we display mistyped spaces as dots so they are more visible
+                    (Span::from("•").red().bold(), typed)
+                } else {
+                    (Span::from(c.to_string()).red().bold(), typed)
+                }
             } else {
-                (Span::from(c.to_string()).red().bold(), typed.unwrap())
-            }
-        } else {
-            (Span::from(c.to_string()).bold(), ' ')
-        };
-        correction_line.push(correction);
-        lesson_letters.push(span);
+                (Span::from(c.to_string()).bold(), ' ')
+            };
+            let span = match ghost_position == Some(idx) && idx != cursor {
+                true => span.bg(Color::DarkGray),
+                false => span,
+            };
+            correction_line.push(correction);
+            lesson_letters.push(span);
+        }
+        lesson_text.push(Line::from(lesson_letters));
+        lesson_text.push(correction_line.italic().gray().into());
     }
 
-    let lesson_line: Line = Line::from(lesson_letters);
-
-    let lesson: Paragraph = Paragraph::new(vec![
-        "\n\n".into(),
-        lesson_line,
-        correction_line.italic().gray().into(),
-    ]).alignment(Alignment::Center);
+    let lesson: Paragraph = Paragraph::new(lesson_text).alignment(Alignment::Center);
     frame.render_widget(
         lesson,
-        layout[2],
+        panels.lesson,
     );
-    if !args.nokb {
-        // TODO: properly indent kb_string
-        let keyboard_display: Paragraph = Paragraph::new(kb_string.to_owned()).gray();
-        let mut indent = 5;
-        if !args.cat {
-            indent = 13;
+
+    // the cat needs room to the right of the keyboard
+    let show_cat = display.cat && panels.lesson.width >= MAX_WIDTH - 2 && panels.full_keyboard;
+    if let Some(keyboard) = panels.keyboard {
+        // the key of the next char is highlighted, the key of a wrong char flashes for a moment
+        let mut highlights: Vec<(char, Style)> = Vec::new();
+        if let Some(next) = lesson_chars.get(typed_chars.len()) {
+            highlights.push((*next, Style::default().fg(Color::Black).bg(Color::Yellow)));
         }
+        if let Some((wrong, ago)) = state.last_miss {
+            if ago.as_millis() < MISS_FLASH_MILLIS {
+                highlights.push((wrong, Style::default().fg(Color::Black).bg(Color::Red)));
+            }
+        }
+        let tints = match display.heatmap.as_str() {
+            "" => Vec::new(),
+            kind => heatmap_tints(keyboard, state.key_stats, kind),
+        };
+        let keyboard_display: Paragraph = Paragraph::new(keyboard_lines(keyboard, &highlights, display.fingers, &tints));
+        let indent = match show_cat {
+            true => 5,
+            false => (panels.lesson.width - keyboard.width()) / 2,
+        };
 
         frame.render_widget(
             keyboard_display,
-            Rect::new(layout[2].x + indent, layout[2].y + 3, layout[2].width - indent, layout[2].height - 4),
+            Rect::new(panels.lesson.x + indent, panels.lesson.y + lesson_height(panels.lesson_lines), keyboard.width(), keyboard.height()),
         );
     }
 
     // draw cursor
-    // get the the offset of the lesson_line since it it centered
-    let lesson_line_offset = layout[2].width as usize / 2 - state.current_lesson_string.len() / 2;
-    let cursor_x = state.current_typed_string.len() + lesson_line_offset;
-    let cursor_y = 1;
+    let cursor_y = 1 + 2 * (cursor_line - first_line);
     frame.set_cursor(
-        layout[2].x + cursor_x as u16,
-        layout[2].y + cursor_y as u16,
+        panels.lesson.x + cursor_x as u16,
+        panels.lesson.y + cursor_y as u16,
     );
 
     // WPM and ACCURACY stats
@@ -165,17 +577,22 @@ fn render(frame: &mut Frame, state: &AppState, args: &Args, kb_string: &String,
     let last_acc = state.acc_history.last().cloned().unwrap_or(0);
     let wpm_width = 3;
     let acc_width = 3;
-    let wpm_line: Line = Line::from(vec![
-        Span::from(format!("  need >= {:width$}WPM,   avg. ", state.need_wpm, width = wpm_width)).gray().into(),
-        Span::from(format!("{:width$}WPM", state.average_wpm, width = wpm_width)).into(),
-        Span::from(",   last" ).gray().into(),
-        Span::from(format!(" {:width$}WPM", last_wpm, width = wpm_width)).into(),
+    let mut wpm_line: Line = Line::from(vec![
+        Span::from(format!("  need >= {:width$}WPM,   avg. ", state.need_wpm, width = wpm_width)).gray(),
+        Span::from(format!("{:width$}WPM", state.average_wpm, width = wpm_width)),
+        Span::from(",   last" ).gray(),
+        Span::from(format!(" {:width$}WPM", last_wpm, width = wpm_width)),
     ]);
+    // in a timed test the last wpm is the net wpm, so the raw wpm goes next to it
+    if let Some(raw_wpm) = state.last_raw_wpm {
+        wpm_line.spans.push(Span::from(", raw").gray());
+        wpm_line.spans.push(Span::from(format!(" {:width$}", raw_wpm, width = wpm_width)));
+    }
     let acc_line: Line = Line::from(vec![
-        Span::from(format!("  need >= {:width$}% Acc, avg. ", state.need_acc, width = acc_width)).gray().into(),
-        Span::from(format!("{:width$}% Acc", state.average_accuracy, width = acc_width)).into(),
-        Span::from(", last" ).gray().into(),
-        Span::from(format!(" {:width$}% Acc", last_acc, width = acc_width)).into(),
+        Span::from(format!("  need >= {:width$}% Acc, avg. ", state.need_acc, width = acc_width)).gray(),
+        Span::from(format!("{:width$}% Acc", state.average_acc, width = acc_width)),
+        Span::from(", last" ).gray(),
+        Span::from(format!(" {:width$}% Acc", last_acc, width = acc_width)),
     ]);
 
     let stats: Paragraph = Paragraph::new(vec![
@@ -184,14 +601,35 @@ fn render(frame: &mut Frame, state: &AppState, args: &Args, kb_string: &String,
     ]);
     frame.render_widget(
         stats,
-        layout[3],
+        panels.stats,
     );
 
-    if args.cat {
+    if let Some(graphs_area) = panels.graphs {
+        let graphs_block: Block = Block::default()
+            .borders(Borders::TOP)
+            .border_set(border::ROUNDED);
+        frame.render_widget(
+            &graphs_block,
+            graphs_area,
+        );
+        let graphs_area = graphs_block.inner(graphs_area);
+        let graphs = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Percentage(50),
+                Constraint::Percentage(50),
+            ])
+            .split(graphs_area.inner(&Margin::new(1, 0)));
+        render_graph(frame, graphs[0], "WPM", state.wpm_history, state.need_wpm, false, Color::Cyan);
+        render_graph(frame, graphs[1], "Accuracy", state.acc_history, state.need_acc, true, Color::Magenta);
+    }
+
+    if show_cat {
         let cat: Paragraph = Paragraph::new(cat_string.to_string()).reset();
+        let (_, cat_height) = text_size(cat_string);
         frame.render_widget(
             cat,
-            Rect::new(layout[2].x + 60, layout[2].y + 5, layout[2].width - 60, layout[2].height-2),
+            Rect::new(panels.lesson.x + 60, panels.lesson.y + lesson_height(panels.lesson_lines) + 2, CAT_WIDTH, cat_height).intersection(frame.size()),
         );
     }
 
@@ -213,39 +651,107 @@ pub fn cleanup_tui() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-pub fn ensure_screen_size(terminal: &mut Terminal<CrosstermBackend<Stdout>>, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+pub fn ensure_screen_size(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<(), Box<dyn std::error::Error>> {
     let size = terminal.size()?;
-    if args.nokb {
-        if size.width < 79 || size.height < 13 {
-            terminal.draw(|frame: &mut Frame| {
-                let warning = Paragraph::new("Please resize your terminal to at least 80x13.\n<esc> or <ctrl-c> to quit").alignment(Alignment::Center);
-                frame.render_widget(
-                    warning,
-                    Rect { x: 0, y: 0, width: size.width, height: size.height },
-                );
-            })?;
-            return Err(io::Error::new(io::ErrorKind::Other, "Terminal too small").into());
-        }
-    } else {
-        // we need more space to display the keyboard
-        if size.width < 79 || size.height < 20 {
-            terminal.draw(|frame: &mut Frame| {
-                let warning = Paragraph::new("Please resize your terminal to at least 80x20,\nor consider disabling the keyboard display with --nokb\n\n<esc> or <ctrl-c> to quit").alignment(Alignment::Center);
-                frame.render_widget(
-                    warning,
-                    Rect { x: 0, y: 0, width: size.width, height: size.height },
-                );
-            })?;
-            return Err(io::Error::new(io::ErrorKind::Other, "Terminal too small").into());
-        }
+    // panels that do not fit are hidden, this is only for terminals too small for the lesson itself
+    if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+        terminal.draw(|frame: &mut Frame| {
+            let warning = Paragraph::new(format!("Please resize your terminal to at least {}x{}.\n<esc> or <ctrl-c> to quit", MIN_WIDTH, MIN_HEIGHT)).alignment(Alignment::Center);
+            frame.render_widget(
+                warning,
+                Rect { x: 0, y: 0, width: size.width, height: size.height },
+            );
+        })?;
+        return Err(io::Error::other("Terminal too small").into());
     }
     Ok(())
 }
 
-pub fn render_tui(state: &AppState, terminal: &mut Terminal<CrosstermBackend<Stdout>>, args: &Args, kb_string: &String, cat_string: &String) -> Result<(), Box<dyn std::error::Error>> {
+pub fn render_tui(state: &Snapshot, terminal: &mut Terminal<CrosstermBackend<Stdout>>, display: &DisplayArgs, keyboards: &Keyboards, ghost: Option<&Ghost>, cat_string: &String) -> Result<(), Box<dyn std::error::Error>> {
+    terminal.draw(|frame: &mut Frame| {
+        render(frame, state, display, keyboards, ghost, cat_string);
+    })?;
+
+    Ok(())
+}
+
+pub fn render_summary(terminal: &mut Terminal<CrosstermBackend<Stdout>>, summary: &Summary) -> Result<(), Box<dyn std::error::Error>> {
     terminal.draw(|frame: &mut Frame| {
-        render(frame, state, args, kb_string, cat_string);
+        let size = frame.size();
+        let lines = summary.lines();
+        let width = size.width.min(MAX_WIDTH);
+        let height = size.height.min(lines.len() as u16 + 4);
+        let area = Rect {
+            x: (size.width - width) / 2,
+            y: (size.height - height) / 2,
+            width,
+            height,
+        };
+
+        let title: Title = Title::from(Line::from(vec![
+            " Session summary ".bold(),
+        ]));
+        let help: Title = Title::from(Line::from(vec![
+            " Exit ".into(),
+            "<any key> ".blue().bold(),
+        ]));
+        let outline: Block = Block::default()
+            .title(title.alignment(Alignment::Center))
+            .title(help.alignment(Alignment::Right))
+            .borders(Borders::ALL)
+            .border_set(border::ROUNDED);
+
+        let text: Vec<Line> = lines.into_iter().map(|line| Line::from(format!("  {}", line))).collect();
+        frame.render_widget(
+            Paragraph::new(text).block(outline.padding(ratatui::widgets::Padding::vertical(1))),
+            area,
+        );
     })?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrap(lesson: &str, width: usize) -> Vec<String> {
+        let chars: Vec<char> = lesson.chars().collect();
+        wrap_lesson(&chars, width).into_iter().map(|line| chars[line].iter().collect()).collect()
+    }
+
+    #[test]
+    fn wrap_lesson_breaks_after_spaces() {
+        assert_eq!(wrap("aaa bb cc ", 7), ["aaa bb ", "cc "]);
+        // a line can be filled up to the last column
+        assert_eq!(wrap("aaa bbb ", 4), ["aaa ", "bbb "]);
+        // the last word does not need a trailing space
+        assert_eq!(wrap("ab cd", 3), ["ab ", "cd"]);
+        assert_eq!(wrap("", 3), [""]);
+    }
+
+    #[test]
+    fn wrap_lesson_splits_words_longer_than_a_line() {
+        assert_eq!(wrap("abcdefghij kl ", 4), ["abcd", "efgh", "ij ", "kl "]);
+        assert_eq!(wrap("ab cdefghij", 4), ["ab ", "cdef", "ghij"]);
+    }
+
+    #[test]
+    fn cursor_moves_to_the_next_line_at_the_end_of_a_line() {
+        // "aaa " and "bb " in a panel of width 9, centered at columns 2 and 3
+        let lines = [0..4, 4..7];
+        assert_eq!(cursor_position(&lines, 0, 2, 9), (0, 0, 2));
+        assert_eq!(cursor_position(&lines, 3, 2, 9), (0, 0, 5));
+        assert_eq!(cursor_position(&lines, 4, 2, 9), (1, 0, 3));
+        // with everything typed, the cursor stays behind the last char
+        assert_eq!(cursor_position(&lines, 7, 2, 9), (1, 0, 6));
+    }
+
+    #[test]
+    fn cursor_line_is_shown_first_until_the_last_lines() {
+        let lines = [0..4, 4..8, 8..12, 12..16];
+        assert_eq!(cursor_position(&lines, 5, 2, 4).1, 1);
+        assert_eq!(cursor_position(&lines, 9, 2, 4).1, 2);
+        assert_eq!(cursor_position(&lines, 13, 2, 4).1, 2);
+    }
+}
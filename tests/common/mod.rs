@@ -0,0 +1,24 @@
+use ngrrram::Options;
+
+// The default options of the command line, with a fixed seed and the given ngram list.
+pub fn options(n: &str) -> Options {
+    Options {
+        n: n.to_string(),
+        top: 50,
+        combi: 4,
+        rep: 3,
+        wpm: 1,
+        acc: 0,
+        chars: vec![],
+        inject: vec![],
+        time: 0,
+        progress: 0,
+        on_fail: "new".to_string(),
+        max_retries: 0,
+        sample: "uniform".to_string(),
+        explore: 0,
+        seed: Some(1),
+        emu_in: String::new(),
+        emu_out: String::new(),
+    }
+}
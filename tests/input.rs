@@ -0,0 +1,96 @@
+// Drives a headless Session with scripted key presses, the way the tui does with the terminal.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use ngrrram::input::{InputSource, KeyCode, KeyInput, ScriptedInput};
+use ngrrram::layout::{KbEmulator, Layout};
+use ngrrram::{history, Key, Session};
+
+fn session(lessons: &[&str]) -> Session {
+    let mut session = Session::new(common::options("2"), history::History::default(), None).unwrap();
+    session.plan_lessons(lessons.iter().map(|lesson| lesson.to_string()).collect());
+    session
+}
+
+// Types the script into the session until it is over and returns what was typed of
+// the current lesson.
+fn type_script(session: &mut Session, emulator: Option<&KbEmulator>, script: &str) -> String {
+    let mut input = ScriptedInput::parse("test", script).unwrap();
+    loop {
+        let Some(key) = input.poll_key(Duration::ZERO).unwrap() else {
+            continue;
+        };
+        if key.is_quit() {
+            break;
+        }
+        if let Some(key) = key.to_session_key(emulator) {
+            session.handle_key(key, Instant::now());
+        }
+    }
+    session.snapshot(Instant::now()).typed.to_string()
+}
+
+#[test]
+fn backspace_deletes_the_last_char() {
+    let mut session = session(&["the cat "]);
+    assert_eq!(type_script(&mut session, None, "0 t\n0 h\n0 x\n0 backspace\n0 e"), "the");
+}
+
+#[test]
+fn delete_word_deletes_back_to_the_last_space() {
+    let mut session = session(&["the cat dog "]);
+    assert_eq!(type_script(&mut session, None, "0 t\n0 h\n0 e\n0 space\n0 c\n0 a\n0 x\n0 ctrl-h"), "the ");
+    // on a space right after a word, the word goes too
+    assert_eq!(type_script(&mut session, None, "0 c\n0 a\n0 t\n0 space\n0 alt-backspace"), "the ");
+    assert_eq!(type_script(&mut session, None, "0 ctrl-h"), "");
+}
+
+#[test]
+fn delete_word_handles_non_ascii_chars() {
+    let mut session = session(&["über für "]);
+    assert_eq!(type_script(&mut session, None, "0 ü\n0 b\n0 e\n0 r\n0 space\n0 ü\n0 ctrl-h"), "über ");
+    assert_eq!(type_script(&mut session, None, "0 f\n0 ü\n0 ctrl-h"), "über ");
+    assert_eq!(type_script(&mut session, None, "0 ü\n0 ctrl-h"), "über ");
+}
+
+#[test]
+fn typing_stops_at_the_end_of_a_non_ascii_lesson() {
+    let mut session = session(&["für "]);
+    assert_eq!(type_script(&mut session, None, "0 f\n0 x\n0 r\n0 space\n0 x"), "fxr ");
+}
+
+#[test]
+fn finishing_a_lesson_starts_the_next() {
+    let mut session = session(&["ab ", "cd "]);
+    assert_eq!(type_script(&mut session, None, "0 a\n0 b\n0 space\n0 c"), "c");
+    let snapshot = session.snapshot(Instant::now());
+    assert_eq!((snapshot.lesson_number, snapshot.passed + snapshot.failed), (2, 1));
+}
+
+#[test]
+fn emulation_maps_typed_chars_to_the_emulated_layout() {
+    let emulator = KbEmulator::new(&Layout::Qwerty, &Layout::Colemak);
+    let mut session = session(&["fFO "]);
+    // e is f on colemak and ; is o, also when shifted
+    assert_eq!(type_script(&mut session, Some(&emulator), "0 e\n0 E\n0 :"), "fFO");
+    assert_eq!(type_script(&mut session, Some(&emulator), "0 enter"), "");
+    assert_eq!(session.snapshot(Instant::now()).passed, 1);
+}
+
+#[test]
+fn emulation_uses_the_base_layout_key() {
+    let emulator = KbEmulator::new(&Layout::Qwerty, &Layout::Colemak);
+    let press = |c: char, base: Option<char>, shifted: bool, caps_lock: bool| {
+        let input = KeyInput { base, shifted, caps_lock, ..KeyInput::new(KeyCode::Char(c)) };
+        input.to_session_key(Some(&emulator))
+    };
+    // the system layout does not matter, ö on qwertz is where ; is on qwerty
+    assert_eq!(press('ö', Some(';'), false, false), Some(Key::Char('o')));
+    assert_eq!(press('Ö', Some(';'), true, false), Some(Key::Char('O')));
+    // caps lock flips the case of the emulated letter
+    assert_eq!(press('E', Some('e'), false, true), Some(Key::Char('F')));
+    assert_eq!(press('e', Some('e'), true, true), Some(Key::Char('f')));
+    // text without a base key, like AltGr+7 on qwertz, is taken as typed
+    assert_eq!(press('{', None, false, false), Some(Key::Char('{')));
+}
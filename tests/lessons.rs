@@ -0,0 +1,78 @@
+// How a session moves on from one lesson to the next.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use ngrrram::{history, Key, Options, Session};
+
+fn session(options: Options, lessons: &[&str]) -> Session {
+    Session::with_lessons(options, lessons.iter().map(|lesson| lesson.to_string()).collect(), history::History::default(), None).unwrap()
+}
+
+// Types the current lesson, a key every 100ms, with a typo fixed right away if it should fail.
+fn type_lesson(session: &mut Session, fail: bool) {
+    let start = Instant::now();
+    let lesson = session.snapshot(start).lesson.to_string();
+    let mut keys: Vec<Key> = lesson.chars().map(Key::Char).collect();
+    if fail {
+        keys.splice(0..0, [Key::Char('z'), Key::Backspace]);
+    }
+    for (i, key) in keys.into_iter().enumerate() {
+        session.handle_key(key, start + Duration::from_millis(100 * i as u64));
+    }
+}
+
+#[test]
+fn failed_lessons_are_repeated_up_to_max_retries() {
+    let options = Options { acc: 100, on_fail: "repeat".into(), max_retries: 2, ..common::options("2") };
+    let mut session = session(options, &["ab ", "cd "]);
+    for retries in 1..=2 {
+        type_lesson(&mut session, true);
+        let snapshot = session.snapshot(Instant::now());
+        assert_eq!((snapshot.lesson, snapshot.lesson_number, snapshot.retries), ("ab ", 1, retries));
+    }
+    // out of retries, the next lesson comes
+    type_lesson(&mut session, true);
+    let snapshot = session.snapshot(Instant::now());
+    assert_eq!((snapshot.lesson, snapshot.lesson_number, snapshot.retries, snapshot.failed), ("cd ", 2, 0, 3));
+}
+
+// The tier after every lesson, starting with the one of the first lesson.
+fn tiers(session: &mut Session, fails: &[bool]) -> Vec<String> {
+    let mut tiers = vec![session.snapshot(Instant::now()).tier.unwrap()];
+    for fail in fails {
+        type_lesson(session, *fail);
+        tiers.push(session.snapshot(Instant::now()).tier.unwrap());
+    }
+    tiers
+}
+
+#[test]
+fn progress_widens_the_pool_then_combines_more_then_moves_to_the_next_list() {
+    let options = Options { top: 150, combi: 3, progress: 1, ..common::options("2") };
+    let mut session = session(options, &["ab "; 4]);
+    assert_eq!(
+        tiers(&mut session, &[false; 4]),
+        ["bi 150x3 (0/1)", "bi 175x3 (0/1)", "bi 200x3 (0/1)", "bi 200x4 (0/1)", "tri 150x3 (0/1)"],
+    );
+}
+
+#[test]
+fn a_failed_lesson_starts_the_count_again() {
+    let options = Options { acc: 100, progress: 2, ..common::options("2") };
+    let mut session = session(options, &["ab "; 4]);
+    assert_eq!(
+        tiers(&mut session, &[false, true, false, false]),
+        ["bi 50x4 (0/2)", "bi 50x4 (1/2)", "bi 50x4 (0/2)", "bi 50x4 (1/2)", "bi 75x4 (0/2)"],
+    );
+}
+
+#[test]
+fn custom_lists_stay_at_their_last_tier() {
+    let path = std::env::temp_dir().join(format!("ngrrram-progress-{}.txt", std::process::id()));
+    std::fs::write(&path, "ab\ncd\n").unwrap();
+    let options = Options { top: 1, progress: 1, ..common::options(path.to_str().unwrap()) };
+    let mut session = session(options, &["ab "; 3]);
+    assert_eq!(tiers(&mut session, &[false; 3]), ["file 1x4 (0/1)", "file 2x4 (0/1)", "file 2x4 (0/1)", "file 2x4 (0/1)"]);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,14 @@
+// Counting ngrams in a text, like the corpus subcommand does.
+use ngrrram::ngrams::{count_ngrams, format_list, CorpusOptions, Unit};
+
+#[test]
+fn counts_ngrams_within_words() {
+    let options = CorpusOptions { fold_case: true, alphabet: None, min_count: 1 };
+    let counted = count_ngrams("Über über, the them", Unit::Chars(3), &options);
+    // ordered by count, ties alphabetically, and never across words
+    assert_eq!(format_list(&counted), "ber 2\nthe 2\nübe 2\nhem 1\n");
+
+    let options = CorpusOptions { fold_case: false, alphabet: None, min_count: 2 };
+    let counted = count_ngrams("the cat the dog", Unit::Words, &options);
+    assert_eq!(counted, vec![("the".to_string(), 2)]);
+}
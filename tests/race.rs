@@ -0,0 +1,40 @@
+// Races pick their ghost from the recorded sessions.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use ngrrram::{history, race, Key, Options, Session};
+
+// Records a session of one lesson typed with the given milliseconds per key.
+fn record(options: Options, millis_per_key: u64) -> ngrrram::recording::Recording {
+    let mut session = Session::new(options, history::History::default(), None).unwrap();
+    let start = Instant::now();
+    let lesson = session.snapshot(start).lesson.to_string();
+    for (i, c) in lesson.chars().enumerate() {
+        session.handle_key(Key::Char(c), start + Duration::from_millis(i as u64 * millis_per_key));
+    }
+    session.recording().clone()
+}
+
+#[test]
+fn best_races_the_fastest_session_with_the_same_options() {
+    let dir = std::env::temp_dir().join(format!("ngrrram-race-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let recordings = [
+        ("slow.json", record(common::options("2"), 300)),
+        ("fast.json", record(Options { seed: Some(2), emu_out: "colemak".into(), ..common::options("2") }, 200)),
+        ("other.json", record(Options { combi: 3, ..common::options("2") }, 100)),
+    ];
+    for (name, recording) in &recordings {
+        std::fs::write(dir.join(name), serde_json::to_string(recording).unwrap()).unwrap();
+    }
+
+    // seed and layouts do not matter, the other options do
+    let options = Options { seed: Some(3), emu_out: "dvorak".into(), ..common::options("2") };
+    let ghost = race::best(&dir, &options).unwrap().unwrap();
+    assert_eq!(ghost.recording.options.seed, Some(2));
+    let options = Options { rep: 1, ..common::options("2") };
+    assert!(race::best(&dir, &options).unwrap().is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
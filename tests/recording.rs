@@ -0,0 +1,69 @@
+// Recordings have to play back on other machines, without the files they were made with.
+mod common;
+
+use std::time::Instant;
+
+use ngrrram::layout::{self, LayoutFile};
+use ngrrram::{history, Key, Session};
+
+#[test]
+fn planned_lessons_need_no_ngram_list() {
+    let missing = "/nonexistent/ngrams.txt";
+    assert!(Session::new(common::options(missing), history::History::default(), None).is_err());
+
+    let lessons = vec!["ab ".to_string()];
+    let mut session = Session::with_lessons(common::options(missing), lessons, history::History::default(), None).unwrap();
+    let now = Instant::now();
+    for c in "ab ".chars() {
+        session.handle_key(Key::Char(c), now);
+    }
+    // without an ngram list, the last lesson comes again
+    let snapshot = session.snapshot(now);
+    assert_eq!((snapshot.lesson_number, snapshot.passed, snapshot.lesson), (2, 1, "ab "));
+}
+
+#[test]
+fn layout_files_keep_their_rows() {
+    let file = LayoutFile {
+        rows: vec!["1234567890-=".into(), "qdrwbjfup;[]".into(), "ashtgyneoi'".into(), "zxmcvkl,./".into()],
+        shift_rows: None,
+    };
+    let rows = LayoutFile::of(&layout::from_file(&file).unwrap()).unwrap();
+    assert_eq!(rows.rows, file.rows);
+    assert_eq!(rows.shift_rows.unwrap()[1], "QDRWBJFUP;[]");
+    assert!(LayoutFile::of(&layout::Layout::Qwerty).is_none());
+}
+
+#[test]
+fn seeds_give_the_same_lessons_everywhere() {
+    // a fixed rng algorithm, so this must never change
+    let options = ngrrram::Options { seed: Some(1234567890), ..common::options("2") };
+    let session = Session::new(options, history::History::default(), None).unwrap();
+    assert_eq!(session.snapshot(Instant::now()).lesson, "an be as ce an be as ce an be as ce ");
+}
+
+#[test]
+fn history_records_generate_their_lesson_again() {
+    let options = ngrrram::Options { chars: vec!["punct".into()], inject: vec!["wrap".into()], ..common::options("2") };
+    let mut session = Session::new(options, history::History::default(), None).unwrap();
+    let now = Instant::now();
+    let lesson = session.snapshot(now).lesson.to_string();
+    for c in lesson.chars() {
+        session.handle_key(Key::Char(c), now);
+    }
+    let record = session.history().lessons[0].clone();
+    let again = Session::new(record.options, history::History::default(), None).unwrap();
+    assert_eq!(again.snapshot(now).lesson, lesson);
+}
+
+#[test]
+fn recordings_of_the_same_second_keep_both() {
+    let dir = std::env::temp_dir().join(format!("ngrrram-recording-{}", std::process::id()));
+    let session = Session::new(common::options("2"), history::History::default(), None).unwrap();
+    let first = ngrrram::recording::save(&dir, session.recording()).unwrap();
+    let second = ngrrram::recording::save(&dir, session.recording()).unwrap();
+    assert_ne!(first, second);
+    assert!(second.to_string_lossy().ends_with("-1.json"));
+    assert!(ngrrram::recording::load(&first).is_ok() && ngrrram::recording::load(&second).is_ok());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,47 @@
+// Timed tests are measured like on common typing sites.
+mod common;
+
+use std::time::{Duration, Instant};
+
+use ngrrram::{history, Key, Options, Session};
+
+#[test]
+fn accuracy_is_the_share_of_right_keystrokes() {
+    let options = Options { time: 15, ..common::options("2") };
+    let mut session = Session::new(options, history::History::default(), None).unwrap();
+    let start = Instant::now();
+    let lesson: Vec<char> = session.snapshot(start).lesson.chars().take(10).collect();
+    // every second keystroke is wrong
+    for (i, c) in lesson.iter().enumerate() {
+        let key = match (i % 2, *c) {
+            (0, c) => c,
+            (_, 'x') => 'y',
+            _ => 'x',
+        };
+        session.handle_key(Key::Char(key), start);
+    }
+    let snapshot = session.tick(start + Duration::from_secs(15));
+    assert_eq!(snapshot.acc_history, &[50]);
+    assert_eq!(snapshot.last_raw_wpm, Some(8)); // 10 keystrokes in a quarter minute
+}
+
+#[test]
+fn backspacing_does_not_restart_the_clock() {
+    let options = Options { time: 15, ..common::options("2") };
+    let mut session = Session::new(options, history::History::default(), None).unwrap();
+    let start = Instant::now();
+    let lesson: Vec<char> = session.snapshot(start).lesson.chars().take(3).collect();
+    for c in &lesson {
+        session.handle_key(Key::Char(*c), start);
+    }
+    for _ in &lesson {
+        session.handle_key(Key::Backspace, start + Duration::from_secs(5));
+    }
+    session.handle_key(Key::Char(lesson[0]), start + Duration::from_secs(10));
+    assert_eq!(session.snapshot(start + Duration::from_secs(12)).time_left, Some(Duration::from_secs(3)));
+    // the test ends on time, even with nothing typed
+    session.handle_key(Key::Backspace, start + Duration::from_secs(11));
+    let snapshot = session.tick(start + Duration::from_secs(15));
+    assert_eq!(snapshot.acc_history.len(), 1);
+    assert_eq!(snapshot.time_left, Some(Duration::from_secs(15)));
+}